@@ -1,8 +1,7 @@
 use std::io::{self, Write};
-use std::net::UdpSocket;
 
-use renet::RenetClient;
-use renet_netcode::{ClientAuthentication, NetcodeClientTransport};
+use client::net::NetClient;
+use protocol::ServerMessage;
 
 fn main() {
     // Prompt for server and username
@@ -35,64 +34,41 @@ fn prompt(label: &str) -> String {
 fn run_client(server_addr: &str, username: &str) {
     println!("Starting (CLI)… connecting to {} as {}", server_addr, username);
 
-    // Create Renet client + UDP transport (nonblocking)
-    let client = RenetClient::new(renet::ConnectionConfig::default());
-    let server_addr = server_addr.parse().expect("Invalid server address");
-
-    let socket = UdpSocket::bind("0.0.0.0:0").expect("bind UDP");
-    socket.set_nonblocking(true).expect("nonblocking");
-
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .expect("time");
-
-    let auth = ClientAuthentication::Unsecure { protocol_id: 0, client_id: 0, server_addr, user_data: None };
-    let transport = NetcodeClientTransport::new(now, auth, socket).expect("netcode client");
-
-    // Send hello right away
-    let payload = serde_json::json!({
-        "event": "Hello",
-        "body": {"name": username},
-    });
-    let bytes = serde_json::to_vec(&payload).unwrap();
-    let mut client = client;
-    let mut transport = transport;
-
-    // First update to kick off connection
-    let mut now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap();
-    let _ = transport.update(now, &mut client);
-    client.send_message(renet::DefaultChannel::ReliableOrdered, bytes);
+    // Connects over UDP and queues Hello right away
+    let Some(mut net) = NetClient::connect(server_addr, username) else {
+        println!("Could not start the client.");
+        return;
+    };
 
     println!("Hello sent. Waiting up to 5s for Welcome…");
 
     let start = std::time::Instant::now();
     let mut got_welcome = false;
+    let mut rejected = None;
 
     loop {
-        now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap();
-        let _ = transport.update(now, &mut client);
-
-        while let Some(bytes) = client.receive_message(renet::DefaultChannel::ReliableOrdered) {
-            if let Ok(value) = serde_json::from_slice::<serde_json::Value>(&bytes) {
-                if let Some(event) = value.get("event").and_then(|v| v.as_str()) {
-                    println!("[client] received event: {} -> {}", event, value);
-                    if event.eq_ignore_ascii_case("welcome") { got_welcome = true; }
-                }
+        for frame in net.poll() {
+            println!("[client] received event at tick {}: {:?}", frame.tick, frame.message);
+            match frame.message {
+                ServerMessage::Welcome { .. } => got_welcome = true,
+                ServerMessage::Rejected { reason } => rejected = Some(reason),
+                _ => {}
             }
         }
 
-        if got_welcome || start.elapsed().as_secs_f32() > 5.0 {
+        if got_welcome || rejected.is_some() || start.elapsed().as_secs_f32() > 5.0 {
             break;
         }
         std::thread::sleep(std::time::Duration::from_millis(16));
     }
 
-    if !got_welcome {
-        println!("No Welcome received. The server may be unreachable or busy. You can still proceed to implement gameplay.");
+    if let Some(reason) = rejected {
+        println!("Server version mismatch: {}", reason);
+    } else if !got_welcome {
+        match net.disconnect_reason() {
+            Some(reason) => println!("No Welcome received ({}).", reason),
+            None => println!("No Welcome received. The server may be unreachable or busy. You can still proceed to implement gameplay."),
+        }
     }
 
     println!("Press ENTER to exit…");
@@ -1,9 +1,15 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::io::{self, Write};
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
-use minifb::{Key, Window, WindowOptions};
-use client::net::NetClient;
-use client::ui::fps::{FpsCounter, draw_text};
+use minifb::{InputCallback, Key, KeyRepeat, Window, WindowOptions};
+use client::net::{ConnectionState, NetClient, RECONNECT_ATTEMPTS};
+use client::prediction::{MapView, Prediction};
+use client::ui::fps::{FpsCounter, draw_text, text_width};
+use protocol::movement::{self, Action, Motion, MovementMode, Pose, StepLimiter, Steering};
+use protocol::{ClientMessage, Difficulty, Facing, LevelInfo, MapSize, RoundEnd, RoundResult, RoundState, ScoreEntry, ServerFrame, ServerMessage, TeamScore, MAX_CHAT_LEN, MAX_HEALTH};
 
 fn main() {
     // Prompt for server and username
@@ -21,57 +27,241 @@ fn main() {
     };
 
     // Wait briefly for Welcome and Map/Init messages
-    let mut got_welcome = false;
-    let mut map: Option<(usize, usize, Vec<u8>)> = None;
-    let mut my_id: Option<u64> = None;
-    let mut players: std::collections::HashMap<u64, (i32, i32)> = std::collections::HashMap::new();
+    let mut state = GameState::default();
 
     let start_wait = std::time::Instant::now();
-    while start_wait.elapsed().as_millis() < 5000 {
-        for msg in net.poll() {
-            if let Some(ev) = msg.get("event").and_then(|v| v.as_str()) {
-                match ev {
-                    "Welcome" | "welcome" => { got_welcome = true; }
-                    "Map" => {
-                        if let Some(body) = msg.get("body") {
-                            let w = body.get("width").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                            let h = body.get("height").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                            if let Some(arr) = body.get("cells").and_then(|v| v.as_array()) {
-                                let cells: Vec<u8> = arr.iter().map(|x| x.as_u64().unwrap_or(0) as u8).collect();
-                                map = Some((w, h, cells));
-                            }
-                        }
-                    }
-                    "PlayerInit" => {
-                        if let Some(body) = msg.get("body") {
-                            if let Some(id) = body.get("id").and_then(|v| v.as_u64()) { my_id = Some(id); }
-                            if let (Some(x), Some(y)) = (body.get("x").and_then(|v| v.as_i64()), body.get("y").and_then(|v| v.as_i64())) {
-                                if let Some(id) = my_id { players.insert(id, (x as i32, y as i32)); }
-                            }
-                        }
-                    }
-                    "Players" => {
-                        if let Some(body) = msg.get("body").and_then(|v| v.as_array()) {
-                            for p in body {
-                                if let (Some(id), Some(x), Some(y)) = (
-                                    p.get("id").and_then(|v| v.as_u64()),
-                                    p.get("x").and_then(|v| v.as_i64()),
-                                    p.get("y").and_then(|v| v.as_i64()),
-                                ) {
-                                    players.insert(id, (x as i32, y as i32));
-                                }
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
+    while start_wait.elapsed().as_millis() < 5000 && state.rejected.is_none() {
+        for frame in net.poll() {
+            state.apply(frame);
         }
         std::thread::sleep(std::time::Duration::from_millis(10));
     }
 
     // Open a tiny window to show the maze and FPS in the title
-    run_window(&server_addr, &username, got_welcome, &mut net, map, my_id, players);
+    run_window(&server_addr, &username, &mut net, state);
+}
+
+/// How long a join/leave notification stays on screen, and how many are shown at once.
+const NOTICE_DURATION: Duration = Duration::from_secs(4);
+const MAX_NOTICES: usize = 4;
+/// How long a chat line stays on screen while not typing, and how many are shown.
+const CHAT_DURATION: Duration = Duration::from_secs(10);
+const MAX_CHAT_LINES: usize = 6;
+/// How long a shot's tracer line stays on screen.
+const TRACER_DURATION: Duration = Duration::from_millis(200);
+
+/// Client-side view of the game, updated from server messages.
+#[derive(Default)]
+struct GameState {
+    connected: bool,
+    /// Reason the server refused our Hello (protocol version mismatch).
+    rejected: Option<String>,
+    map: Option<(usize, usize, Vec<u8>)>,
+    /// Goal cell of the current maze, when the server plays with one.
+    exit: Option<(usize, usize)>,
+    my_id: Option<u64>,
+    players: HashMap<u64, Pose>,
+    /// Our own position ahead of the server; drawn instead of the replicated one.
+    prediction: Prediction,
+    /// Names from `PlayerJoined`, for players who joined after us.
+    names: HashMap<u64, String>,
+    /// Short-lived join/leave notifications, oldest first.
+    notices: VecDeque<(String, Instant)>,
+    /// Recent chat, oldest first.
+    chat: VecDeque<ChatLine>,
+    /// Recent shots, drawn as tracer lines.
+    tracers: Vec<Tracer>,
+    health: u32,
+    /// Between our `PlayerKilled` and the `PlayerInit` of the respawn.
+    dead: bool,
+    round: Option<RoundState>,
+    /// Playlist level being played, on servers with a playlist.
+    level: Option<LevelInfo>,
+    /// Server tick at which the current round state ends.
+    round_end_tick: u64,
+    /// Server ticks per second, from `Welcome`.
+    tick_hz: u32,
+    /// Latest standings, best first.
+    scoreboard: Vec<ScoreEntry>,
+    /// Team totals, best first; empty in free-for-all.
+    team_scores: Vec<TeamScore>,
+    /// Team of each player, in team modes.
+    teams: HashMap<u64, u8>,
+    /// Grid steps or continuous movement, from `Welcome`.
+    movement: MovementMode,
+    /// Exact positions in continuous movement, drawn instead of the cell centres.
+    motions: HashMap<u64, Motion>,
+    /// Ready players and votes, while in the lobby.
+    lobby: Option<Lobby>,
+}
+
+/// Last `LobbyStatus` from the server.
+struct Lobby {
+    ready: Vec<u64>,
+    needed: u32,
+    difficulty_votes: Vec<(Difficulty, u32)>,
+    size_votes: Vec<(MapSize, u32)>,
+    level_votes: Vec<(u32, u32)>,
+}
+
+/// A shot from one cell to another, as reported by `ShotFired`.
+struct Tracer {
+    from: (usize, usize),
+    to: (usize, usize),
+    at: Instant,
+}
+
+impl GameState {
+    /// Applies one server message. Returns true when the maze changed and needs a redraw.
+    fn apply(&mut self, frame: ServerFrame) -> bool {
+        match frame.message {
+            ServerMessage::Welcome { resumed, tick_hz, movement, .. } => {
+                self.tick_hz = tick_hz;
+                self.movement = movement;
+                if resumed && self.connected { self.notify("reconnected, session resumed".into()); }
+                self.connected = true;
+            }
+            ServerMessage::Rejected { reason } => { self.rejected = Some(reason); }
+            ServerMessage::Round { state, level, remaining_ms, .. } => {
+                // Counted in server ticks, so delivery delay does not stretch the countdown
+                self.round = Some(state);
+                if let Some(level) = level.as_ref().filter(|_| state == RoundState::InRound) {
+                    self.notify(format!("level {}: {}", level.number, level.name));
+                }
+                self.level = level;
+                if state != RoundState::Lobby { self.lobby = None; }
+                self.round_end_tick = frame.tick + remaining_ms as u64 * self.tick_hz as u64 / 1000;
+            }
+            ServerMessage::PlayerJoined { id, name } => {
+                if Some(id) != self.my_id { self.notify(format!("{} joined", name)); }
+                self.names.insert(id, name);
+            }
+            ServerMessage::PlayerLeft { id, reason } => {
+                self.players.remove(&id);
+                let name = self.names.remove(&id).unwrap_or_else(|| format!("player {}", id));
+                self.notify(format!("{} left ({})", name, reason));
+            }
+            ServerMessage::PlayerInit(p) => {
+                // Sent on join and on every respawn
+                self.my_id = Some(p.id);
+                self.health = MAX_HEALTH;
+                self.dead = false;
+                self.prediction.reset(p);
+                self.players.insert(p.id, p.pose());
+                if let Some(team) = p.team { self.teams.insert(p.id, team); }
+                if let Some(motion) = p.motion { self.motions.insert(p.id, motion); }
+            }
+            ServerMessage::Snapshot(snapshot) => {
+                // NetClient hands us full snapshots; players missing from it are gone
+                self.players = snapshot.changed.iter().map(|p| (p.id, p.pose())).collect();
+                self.teams.extend(snapshot.changed.iter().filter_map(|p| Some((p.id, p.team?))));
+                self.motions = snapshot.changed.iter().filter_map(|p| Some((p.id, p.motion?))).collect();
+                let me = snapshot.changed.iter().find(|p| Some(p.id) == self.my_id);
+                if let (Some(me), Some((width, height, cells))) = (me, self.map.as_ref()) {
+                    let map = MapView { width: *width, height: *height, cells };
+                    self.prediction.reconcile(*me, &map);
+                }
+                self.show_prediction();
+            }
+            ServerMessage::ShotFired { from, to, .. } => {
+                self.tracers.push(Tracer { from, to, at: Instant::now() });
+            }
+            ServerMessage::PlayerHit { shooter, target, health } => {
+                if Some(target) == self.my_id { self.health = health; }
+                if Some(shooter) == self.my_id {
+                    let name = self.name(target);
+                    self.notify(format!("you hit {}", name));
+                } else if Some(target) == self.my_id {
+                    let name = self.name(shooter);
+                    self.notify(format!("{} hit you", name));
+                }
+            }
+            ServerMessage::PlayerKilled { killer, victim } => {
+                self.players.remove(&victim);
+                if Some(victim) == self.my_id { self.dead = true; }
+                let text = format!("{} killed {}", self.name(killer), self.name(victim));
+                self.notify(text);
+            }
+            ServerMessage::Scoreboard { entries, teams } => {
+                // Names of players who were here before us only arrive this way
+                for e in &entries { self.names.insert(e.id, e.name.clone()); }
+                self.teams = entries.iter().filter_map(|e| Some((e.id, e.team?))).collect();
+                self.scoreboard = entries;
+                self.team_scores = teams;
+            }
+            ServerMessage::LobbyStatus { ready, needed, difficulty_votes, size_votes, level_votes } => {
+                self.lobby = Some(Lobby { ready, needed, difficulty_votes, size_votes, level_votes });
+            }
+            ServerMessage::RoundResult(result) => {
+                let text = self.describe(&result);
+                self.notify(text);
+            }
+            ServerMessage::History { rounds } => {
+                if rounds.is_empty() { self.notify("no rounds played yet".into()); }
+                for result in rounds.iter().rev().take(MAX_NOTICES).rev() {
+                    let text = self.describe(result);
+                    self.notify(text);
+                }
+            }
+            ServerMessage::Chat { from, name, text, team_only, sent_at_ms } => {
+                // Server clock, shown as UTC hours and minutes
+                let minutes = sent_at_ms / 60_000;
+                let stamp = format!("[{:02}:{:02}]", minutes / 60 % 24, minutes % 60);
+                let (text, color) = match (from, team_only) {
+                    (None, _) => (text, rgb(255, 220, 120)),
+                    (Some(_), true) => (format!("{} (team) {}: {}", stamp, name, text), rgb(120, 255, 120)),
+                    (Some(_), false) => (format!("{} {}: {}", stamp, name, text), rgb(235, 235, 235)),
+                };
+                if self.chat.len() == MAX_CHAT_LINES { self.chat.pop_front(); }
+                self.chat.push_back(ChatLine { text, color, at: Instant::now() });
+            }
+            ServerMessage::Map { width, height, cells, exit, .. } => {
+                self.map = Some((width, height, cells));
+                self.exit = exit;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn name(&self, id: u64) -> String {
+        self.names.get(&id).cloned().unwrap_or_else(|| format!("player {}", id))
+    }
+
+    fn describe(&self, result: &RoundResult) -> String {
+        let how = match result.reason {
+            RoundEnd::ScoreLimit => "score limit",
+            RoundEnd::LastStanding => "last standing",
+            RoundEnd::ReachedExit => "reached the exit",
+            RoundEnd::TimeLimit => "time",
+        };
+        match (result.winner, result.team) {
+            (Some(id), _) => format!("round {}: {} won ({}, {}s)", result.round, self.name(id), how, result.duration_ms / 1000),
+            (None, Some(team)) => format!("round {}: team {} won ({}, {}s)", result.round, team + 1, how, result.duration_ms / 1000),
+            (None, None) => format!("round {}: draw ({}, {}s)", result.round, how, result.duration_ms / 1000),
+        }
+    }
+
+    fn notify(&mut self, text: String) {
+        if self.notices.len() == MAX_NOTICES { self.notices.pop_front(); }
+        self.notices.push_back((text, Instant::now()));
+    }
+
+    /// Moves our dot right away instead of waiting for the server's snapshot.
+    fn predict(&mut self, seq: u32, action: Action) {
+        if let Some((width, height, cells)) = self.map.as_ref() {
+            let map = MapView { width: *width, height: *height, cells };
+            self.prediction.input(seq, action, &map);
+        }
+        self.show_prediction();
+    }
+
+    fn show_prediction(&mut self) {
+        if self.dead { return; }
+        if let (Some(id), Some(pose)) = (self.my_id, self.prediction.pose()) {
+            self.players.insert(id, pose);
+        }
+    }
 }
 
 fn prompt(label: &str) -> String {
@@ -87,11 +277,8 @@ fn prompt(label: &str) -> String {
 fn run_window(
     server_addr: &str,
     username: &str,
-    mut connected: bool,
     net: &mut NetClient,
-    mut map: Option<(usize, usize, Vec<u8>)>,
-    mut my_id: Option<u64>,
-    mut players: std::collections::HashMap<u64, (i32, i32)>,
+    mut state: GameState,
 ) {
     const WIDTH: usize = 400;
     const HEIGHT: usize = 300;
@@ -99,18 +286,18 @@ fn run_window(
     let mut window = Window::new(
         &format!(
             "MazeWars Client — {} | {} | FPS: --",
-            if connected { "connected" } else { "no welcome" },
+            if state.connected { "connected" } else if state.rejected.is_some() { "rejected" } else { "no welcome" },
             username
         ),
-        WIDTH as usize,
-        HEIGHT as usize,
+        WIDTH,
+        HEIGHT,
         WindowOptions::default(),
     )
     .expect("Unable to open window");
 
     let mut buffer = vec![0u32; WIDTH * HEIGHT];
     // Initial draw
-    if let Some((mw, mh, cells)) = map.as_ref() {
+    if let Some((mw, mh, cells)) = state.map.as_ref() {
         draw_maze_into(&mut buffer, WIDTH, HEIGHT, *mw, *mh, cells);
     } else {
         draw_background(&mut buffer, WIDTH, HEIGHT);
@@ -118,58 +305,77 @@ fn run_window(
 
     let mut last_title_update = Instant::now();
     let mut fps_counter = FpsCounter::new();
+    let mut last_move = Instant::now();
+    let mut limiter = StepLimiter::default();
+    let mut steering = Steering::default();
+    // Text typed into the window, and the chat line being edited (with its team-only flag)
+    let typed = Rc::new(RefCell::new(String::new()));
+    window.set_input_callback(Box::new(TypedText(typed.clone())));
+    let mut chat_line: Option<(String, bool)> = None;
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        // Input: WASD -> send Input to server (grid steps)
-        let mut dx = 0;
-        let mut dy = 0;
-        if window.is_key_down(Key::W) { dy -= 1; }
-        if window.is_key_down(Key::S) { dy += 1; }
-        if window.is_key_down(Key::A) { dx -= 1; }
-        if window.is_key_down(Key::D) { dx += 1; }
-        if dx != 0 || dy != 0 { net.send_input(dx, dy); }
+    while window.is_open() {
+        // Escape closes the chat line first, the window second
+        if window.is_key_pressed(Key::Escape, KeyRepeat::No) && chat_line.take().is_none() {
+            break;
+        }
+        // Enter opens the chat line (T for team chat) and sends it; keys go nowhere else meanwhile
+        let text: String = typed.borrow_mut().drain(..).collect();
+        let enter = window.is_key_pressed(Key::Enter, KeyRepeat::No);
+        if let Some((line, team_only)) = chat_line.as_mut() {
+            line.extend(text.chars().filter(|c| !c.is_control()));
+            if window.is_key_pressed(Key::Backspace, KeyRepeat::Yes) { line.pop(); }
+            *line = line.chars().take(MAX_CHAT_LEN).collect();
+            if enter {
+                if !line.trim().is_empty() {
+                    net.send(&ClientMessage::Chat { text: line.clone(), team_only: *team_only });
+                }
+                chat_line = None;
+            }
+        } else if enter {
+            chat_line = Some((String::new(), false));
+        } else if window.is_key_pressed(Key::T, KeyRepeat::No) && state.my_id.is_some_and(|id| state.teams.contains_key(&id)) {
+            chat_line = Some((String::new(), true));
+        }
+        let typing = chat_line.is_some();
+
+        // Input: turns fire once per key press, moves repeat every STEP_INTERVAL while held.
+        // Everything goes through the same limiter the server applies, so nothing is refused.
+        let action = read_action(&window, last_move.elapsed() >= movement::STEP_INTERVAL)
+            .filter(|_| !state.dead && !typing && state.movement == MovementMode::Grid);
+        if let Some(action) = action {
+            if limiter.try_take() {
+                let seq = net.send_input(action);
+                state.predict(seq, action);
+                if is_move(action) { last_move = Instant::now(); }
+            }
+        }
+        // Continuous movement: the server keeps applying the keys until they change
+        if state.movement == MovementMode::Continuous {
+            let held = if typing { Steering::default() } else { read_steering(&window) };
+            if held != steering {
+                steering = held;
+                net.send_steer(steering);
+            }
+        }
+        // The server enforces the fire cooldown; early shots are simply ignored
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) && !state.dead && !typing {
+            net.send(&ClientMessage::Shoot);
+        }
+        if window.is_key_pressed(Key::H, KeyRepeat::No) && !typing {
+            net.send(&ClientMessage::QueryHistory);
+        }
+        if let Some(lobby) = state.lobby.as_ref().filter(|_| !typing) {
+            if let Some(msg) = read_lobby_input(&window, lobby, state.my_id) {
+                net.send(&msg);
+            }
+        }
 
         // Poll network updates
-        for msg in net.poll() {
-            if let Some(ev) = msg.get("event").and_then(|v| v.as_str()) {
-                match ev {
-                    "Welcome" | "welcome" => { connected = true; }
-                    "Round" => { /* could show round/difficulty in title next tick */ }
-                    "PlayerInit" => {
-                        if let Some(body) = msg.get("body") {
-                            if let Some(id) = body.get("id").and_then(|v| v.as_u64()) { my_id = Some(id); }
-                            if let (Some(x), Some(y)) = (body.get("x").and_then(|v| v.as_i64()), body.get("y").and_then(|v| v.as_i64())) {
-                                if let Some(id) = my_id { players.insert(id, (x as i32, y as i32)); }
-                            }
-                        }
-                    }
-                    "Players" => {
-                        if let Some(body) = msg.get("body").and_then(|v| v.as_array()) {
-                            for p in body {
-                                if let (Some(id), Some(x), Some(y)) = (
-                                    p.get("id").and_then(|v| v.as_u64()),
-                                    p.get("x").and_then(|v| v.as_i64()),
-                                    p.get("y").and_then(|v| v.as_i64()),
-                                ) {
-                                    players.insert(id, (x as i32, y as i32));
-                                }
-                            }
-                        }
-                    }
-                    "Map" => {
-                        if let Some(body) = msg.get("body") {
-                            let mw = body.get("width").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                            let mh = body.get("height").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                            if let Some(arr) = body.get("cells").and_then(|v| v.as_array()) {
-                                let cells: Vec<u8> = arr.iter().map(|x| x.as_u64().unwrap_or(0) as u8).collect();
-                                // Redraw base layer immediately
-                                draw_maze_into(&mut buffer, WIDTH, HEIGHT, mw, mh, &cells);
-                                // Store latest map
-                                map = Some((mw, mh, cells));
-                            }
-                        }
-                    }
-                    _ => {}
+        for frame in net.poll() {
+            if state.apply(frame) {
+                // Redraw base layer immediately
+                if let Some((mw, mh, cells)) = state.map.as_ref() {
+                    draw_maze_into(&mut buffer, WIDTH, HEIGHT, *mw, *mh, cells);
                 }
             }
         }
@@ -180,7 +386,13 @@ fn run_window(
         if last_title_update.elapsed() >= Duration::from_millis(250) {
             window.set_title(&format!(
                 "MazeWars Client — {} | user: {} | server: {} | FPS: {}",
-                if connected { "connected" } else { "no welcome" },
+                match net.state() {
+                    ConnectionState::Connected => "connected",
+                    ConnectionState::Connecting => "no welcome",
+                    ConnectionState::Reconnecting { .. } => "reconnecting",
+                    ConnectionState::Disconnected(_) if state.rejected.is_some() => "rejected",
+                    ConnectionState::Disconnected(_) => "disconnected",
+                },
                 username,
                 server_addr,
                 fps_counter.fps
@@ -190,23 +402,120 @@ fn run_window(
 
         // Redraw player dots on a copy of the background
         let mut frame = buffer.clone();
-        if let Some((mw, mh, _)) = map.as_ref() {
-            draw_players(&mut frame, WIDTH, HEIGHT, *mw, *mh, &players, my_id);
+        if let (Some((mw, mh, _)), Some(exit)) = (state.map.as_ref(), state.exit) {
+            draw_exit(&mut frame, WIDTH, HEIGHT, *mw, *mh, exit);
+        }
+        if let Some((mw, mh, _)) = state.map.as_ref() {
+            draw_players(&mut frame, WIDTH, HEIGHT, *mw, *mh, &state, state.my_id);
         } else {
-            draw_players(&mut frame, WIDTH, HEIGHT, 1, 1, &players, my_id);
+            draw_players(&mut frame, WIDTH, HEIGHT, 1, 1, &state, state.my_id);
+        }
+        state.tracers.retain(|t| t.at.elapsed() < TRACER_DURATION);
+        if let Some((mw, mh, _)) = state.map.as_ref() {
+            for t in &state.tracers {
+                draw_tracer(&mut frame, WIDTH, HEIGHT, *mw, *mh, t.from, t.to);
+            }
         }
         // On-screen overlays
+        if let Some(reason) = state.rejected.as_deref() {
+            draw_version_mismatch(&mut frame, WIDTH, HEIGHT, reason);
+        }
     let fg = rgb(0, 255, 128);
     let bg = Some(rgb(0, 0, 0));
-    let mut frame = frame; // mutable binding
     draw_text(&mut frame, WIDTH, 6, 6, &format!("FPS:{}", fps_counter.fps), fg, bg);
-        if !connected {
-            draw_text(&mut frame, WIDTH, 6, 18, "waiting for server...", rgb(220,220,220), Some(rgb(30,30,30)));
+        if state.connected {
+            let mut status = match state.dead {
+                true => "DEAD - respawning...".to_string(),
+                false => format!("HP {}/{}", state.health, MAX_HEALTH),
+            };
+            if let Some(team) = state.my_id.and_then(|id| state.teams.get(&id)) {
+                status = format!("{}  TEAM {}", status, team + 1);
+            }
+            draw_text(&mut frame, WIDTH, 60, 6, &status, rgb(255, 120, 120), bg);
+        }
+        if let (Some(round), true) = (state.round, state.tick_hz > 0) {
+            let secs = state.round_end_tick.saturating_sub(net.server_tick()).div_ceil(state.tick_hz as u64);
+            let label = match round {
+                RoundState::Lobby => "LOBBY",
+                RoundState::InRound => "ROUND",
+                RoundState::Intermission => "INTERMISSION",
+            };
+            let mut line = format!("{} {}:{:02}", label, secs / 60, secs % 60);
+            if let Some(level) = &state.level {
+                line = format!("{}  LEVEL {} {}", line, level.number, level.name);
+            }
+            draw_text(&mut frame, WIDTH, 6, 30, &line, rgb(220, 220, 220), bg);
+        }
+        if let Some(lobby) = &state.lobby {
+            draw_lobby(&mut frame, WIDTH, lobby, state.my_id);
+        }
+        // Standings while Tab is held, and always between rounds
+        if window.is_key_down(Key::Tab) || state.round == Some(RoundState::Intermission) {
+            draw_scoreboard(&mut frame, WIDTH, &state.scoreboard, &state.team_scores, state.my_id);
+        }
+        if state.rejected.is_none() {
+            match net.state() {
+                ConnectionState::Connected => {}
+                ConnectionState::Connecting => {
+                    draw_text(&mut frame, WIDTH, 6, 18, "waiting for server...", rgb(220,220,220), Some(rgb(30,30,30)));
+                }
+                ConnectionState::Reconnecting { attempt } => {
+                    let text = format!("connection lost, reconnecting ({}/{})...", attempt, RECONNECT_ATTEMPTS);
+                    draw_text(&mut frame, WIDTH, 6, 18, &text, rgb(255,200,120), Some(rgb(30,30,30)));
+                }
+                ConnectionState::Disconnected(reason) => {
+                    draw_text(&mut frame, WIDTH, 6, 18, &format!("disconnected: {}", reason), rgb(255,120,120), Some(rgb(30,30,30)));
+                }
+            }
+        }
+
+        // Chat above the notices; everything recent stays up while typing
+        let chat_y = HEIGHT - 20 - MAX_NOTICES * 10;
+        if let Some((line, team_only)) = &chat_line {
+            let prompt = if *team_only { "team" } else { "say" };
+            draw_text(&mut frame, WIDTH, 6, chat_y, &format!("{}: {}_", prompt, line), rgb(255, 255, 255), Some(rgb(30, 30, 30)));
+        }
+        let shown = state.chat.iter().rev().filter(|l| typing || l.at.elapsed() < CHAT_DURATION);
+        for (i, line) in shown.enumerate() {
+            draw_text(&mut frame, WIDTH, 6, chat_y - 12 - i * 10, &line.text, line.color, Some(rgb(30, 30, 30)));
+        }
+
+        state.notices.retain(|(_, at)| at.elapsed() < NOTICE_DURATION);
+        for (i, (text, _)) in state.notices.iter().rev().enumerate() {
+            draw_text(&mut frame, WIDTH, 6, HEIGHT - 14 - i * 10, text, rgb(255, 220, 120), Some(rgb(30, 30, 30)));
         }
 
     window.update_with_buffer(&frame, WIDTH, HEIGHT).unwrap();
         std::thread::sleep(Duration::from_millis(10)); // ~100 FPS cap, enough to show >50
     }
+    net.disconnect();
+}
+
+struct ChatLine {
+    text: String,
+    color: u32,
+    at: Instant,
+}
+
+/// Collects the characters typed into the window, for the chat line.
+struct TypedText(Rc<RefCell<String>>);
+
+impl InputCallback for TypedText {
+    fn add_char(&mut self, uni_char: u32) {
+        if let Some(c) = char::from_u32(uni_char) {
+            self.0.borrow_mut().push(c);
+        }
+    }
+}
+
+/// Full-screen notice shown instead of the maze when the server refused our protocol version.
+fn draw_version_mismatch(buf: &mut [u32], w: usize, h: usize, reason: &str) {
+    for px in buf.iter_mut() { *px = rgb(40, 10, 10); }
+    let title = "SERVER VERSION MISMATCH";
+    draw_text(buf, w, w.saturating_sub(text_width(title)) / 2, h / 2 - 20, title, rgb(255, 90, 90), None);
+    draw_text(buf, w, w.saturating_sub(text_width(reason)) / 2, h / 2, reason, rgb(230, 230, 230), None);
+    let hint = "update your client and reconnect";
+    draw_text(buf, w, w.saturating_sub(text_width(hint)) / 2, h / 2 + 14, hint, rgb(160, 160, 160), None);
 }
 
 fn draw_background(buf: &mut [u32], w: usize, h: usize) {
@@ -230,7 +539,7 @@ fn draw_background(buf: &mut [u32], w: usize, h: usize) {
     // No fake player dot here to avoid confusion before connection
 }
 
-fn draw_maze_into(buf: &mut [u32], w: usize, h: usize, mw: usize, mh: usize, cells: &Vec<u8>) {
+fn draw_maze_into(buf: &mut [u32], w: usize, h: usize, mw: usize, mh: usize, cells: &[u8]) {
     // Dark background
     for px in buf.iter_mut() { *px = rgb(16, 16, 20); }
     // Compute cell size to fit
@@ -254,19 +563,151 @@ fn draw_maze_into(buf: &mut [u32], w: usize, h: usize, mw: usize, mh: usize, cel
     }
 }
 
-fn draw_players(buf: &mut [u32], w: usize, h: usize, mw: usize, mh: usize, players: &std::collections::HashMap<u64, (i32, i32)>, my_id: Option<u64>) {
+fn draw_exit(buf: &mut [u32], w: usize, h: usize, mw: usize, mh: usize, (x, y): (usize, usize)) {
+    let cw = (w as f32 / mw as f32).floor().max(1.0) as usize;
+    let ch = (h as f32 / mh as f32).floor().max(1.0) as usize;
+    let color = rgb(40, 160, 70);
+    for py in y * ch..((y + 1) * ch).min(h) {
+        for px in x * cw..((x + 1) * cw).min(w) {
+            buf[py * w + px] = color;
+        }
+    }
+}
+
+fn draw_players(buf: &mut [u32], w: usize, h: usize, mw: usize, mh: usize, state: &GameState, my_id: Option<u64>) {
     let me_color = rgb(0, 200, 255);
     let cw = (w as f32 / mw as f32).floor().max(1.0) as usize;
     let ch = (h as f32 / mh as f32).floor().max(1.0) as usize;
-    for (id, (x, y)) in players {
-        let px = (*x as usize) * cw + cw / 2;
-        let py = (*y as usize) * ch + ch / 2;
-        let color = if Some(*id) == my_id { me_color } else { color_from_id(*id) };
-        draw_disc(buf, w, px as isize, py as isize, (cw.min(ch).max(3) / 3) as isize, color);
+    for (id, pose) in &state.players {
+        let (px, py) = match state.motions.get(id) {
+            Some(m) => ((m.x * cw as f32) as usize, (m.y * ch as f32) as usize),
+            None => (pose.x * cw + cw / 2, pose.y * ch + ch / 2),
+        };
+        let color = if Some(*id) == my_id { me_color } else { color_from_id(*id, state.teams.get(id).copied()) };
+        let r = cw.min(ch).max(3) / 3;
+        draw_disc(buf, w, px as isize, py as isize, r as isize, color);
+        // Facing indicator: a short tick from the dot's centre to just past its edge
+        let len = r + 2;
+        match pose.facing {
+            Facing::North => draw_vline(buf, w, px, py.saturating_sub(len), py, color),
+            Facing::South => draw_vline(buf, w, px, py, py + len, color),
+            Facing::West => draw_hline(buf, w, py, px.saturating_sub(len), px, color),
+            Facing::East => draw_hline(buf, w, py, px, px + len, color),
+        }
+    }
+}
+
+/// Lobby controls: R toggles ready, 1-3 vote easy/medium/hard, 4-6 vote small/medium/large.
+fn read_lobby_input(window: &Window, lobby: &Lobby, my_id: Option<u64>) -> Option<ClientMessage> {
+    let pressed = |key| window.is_key_pressed(key, KeyRepeat::No);
+    if pressed(Key::R) {
+        let ready = my_id.is_some_and(|id| lobby.ready.contains(&id));
+        return Some(ClientMessage::Ready { ready: !ready });
+    }
+    let difficulty_keys = [Key::Key1, Key::Key2, Key::Key3];
+    if let Some(i) = difficulty_keys.iter().position(|k| pressed(*k)) {
+        return Some(ClientMessage::VoteDifficulty { difficulty: Difficulty::ALL[i] });
+    }
+    let size_keys = [Key::Key4, Key::Key5, Key::Key6];
+    if let Some(i) = size_keys.iter().position(|k| pressed(*k)) {
+        return Some(ClientMessage::VoteMapSize { size: MapSize::ALL[i] });
+    }
+    None
+}
+
+fn draw_lobby(buf: &mut [u32], w: usize, lobby: &Lobby, my_id: Option<u64>) {
+    let bg = Some(rgb(30, 30, 30));
+    let me_ready = my_id.is_some_and(|id| lobby.ready.contains(&id));
+    let ready = format!(
+        "R: {} ({}/{} ready to start)",
+        if me_ready { "not ready" } else { "ready" },
+        lobby.ready.len(),
+        lobby.needed
+    );
+    let difficulty: Vec<String> = lobby.difficulty_votes.iter().map(|(d, n)| format!("{} {}", d.as_str(), n)).collect();
+    let size: Vec<String> = lobby.size_votes.iter().map(|(s, n)| format!("{} {}", s.as_str(), n)).collect();
+    draw_text(buf, w, 6, 42, &ready, if me_ready { rgb(120, 255, 120) } else { rgb(255, 220, 120) }, bg);
+    draw_text(buf, w, 6, 54, &format!("1-3 difficulty: {}", difficulty.join("  ")), rgb(220, 220, 220), bg);
+    draw_text(buf, w, 6, 66, &format!("4-6 size: {}", size.join("  ")), rgb(220, 220, 220), bg);
+    if !lobby.level_votes.is_empty() {
+        let levels: Vec<String> = lobby.level_votes.iter().map(|(l, n)| format!("#{} {}", l, n)).collect();
+        draw_text(buf, w, 6, 78, &format!("/vote level: {}", levels.join("  ")), rgb(220, 220, 220), bg);
     }
 }
 
-fn color_from_id(id: u64) -> u32 {
+fn draw_scoreboard(buf: &mut [u32], w: usize, entries: &[ScoreEntry], teams: &[TeamScore], my_id: Option<u64>) {
+    let x = w.saturating_sub(200);
+    draw_text(buf, w, x, 6, "NAME         K   D  SCORE", rgb(255, 255, 255), Some(rgb(30, 30, 30)));
+    for (i, e) in entries.iter().enumerate() {
+        let line = format!("{:<10} {:>3} {:>3} {:>6}", e.name.chars().take(10).collect::<String>(), e.kills, e.deaths, e.score);
+        let color = match e.team {
+            _ if Some(e.id) == my_id => rgb(0, 200, 255),
+            Some(team) => color_from_id(e.id, Some(team)),
+            None => rgb(220, 220, 220),
+        };
+        draw_text(buf, w, x, 18 + i * 10, &line, color, Some(rgb(30, 30, 30)));
+    }
+    // Team totals below the players
+    let top = 24 + entries.len() * 10;
+    for (i, t) in teams.iter().enumerate() {
+        let line = format!("{:<10} {:>3} {:>3} {:>6}", format!("TEAM {}", t.team + 1), t.kills, t.deaths, t.score);
+        draw_text(buf, w, x, top + i * 10, &line, color_from_id(0, Some(t.team)), Some(rgb(30, 30, 30)));
+    }
+}
+
+/// Shots travel straight along a row or column, so the tracer is one line between cell centres.
+fn draw_tracer(buf: &mut [u32], w: usize, h: usize, mw: usize, mh: usize, from: (usize, usize), to: (usize, usize)) {
+    let color = rgb(255, 240, 90);
+    let cw = (w as f32 / mw as f32).floor().max(1.0) as usize;
+    let ch = (h as f32 / mh as f32).floor().max(1.0) as usize;
+    let (x0, y0) = (from.0 * cw + cw / 2, from.1 * ch + ch / 2);
+    let (x1, y1) = (to.0 * cw + cw / 2, to.1 * ch + ch / 2);
+    if y0 == y1 {
+        draw_hline(buf, w, y0, x0.min(x1), x0.max(x1), color);
+    } else {
+        draw_vline(buf, w, x0, y0.min(y1), y0.max(y1), color);
+    }
+}
+
+/// Maze War controls: W/S or Up/Down step forward/back, A/D or Left/Right turn, Q/E strafe,
+/// X turns around. `move_ready` is false while the last move's repeat delay is running.
+fn read_action(window: &Window, move_ready: bool) -> Option<Action> {
+    let pressed = |keys: &[Key]| keys.iter().any(|k| window.is_key_pressed(*k, KeyRepeat::No));
+    let held = |keys: &[Key]| keys.iter().any(|k| window.is_key_down(*k));
+    if pressed(&[Key::A, Key::Left]) { return Some(Action::TurnLeft); }
+    if pressed(&[Key::D, Key::Right]) { return Some(Action::TurnRight); }
+    if pressed(&[Key::X]) { return Some(Action::AboutFace); }
+    if !move_ready { return None; }
+    if held(&[Key::W, Key::Up]) { return Some(Action::Forward); }
+    if held(&[Key::S, Key::Down]) { return Some(Action::Back); }
+    if held(&[Key::Q]) { return Some(Action::StrafeLeft); }
+    if held(&[Key::E]) { return Some(Action::StrafeRight); }
+    None
+}
+
+/// Held keys as continuous steering: W/S move, Q/E strafe, A/D or the arrows turn.
+fn read_steering(window: &Window) -> Steering {
+    let held = |keys: &[Key]| keys.iter().any(|k| window.is_key_down(*k));
+    let axis = |neg: &[Key], pos: &[Key]| held(pos) as i32 as f32 - held(neg) as i32 as f32;
+    Steering {
+        forward: axis(&[Key::S, Key::Down], &[Key::W, Key::Up]),
+        strafe: axis(&[Key::Q], &[Key::E]),
+        turn: axis(&[Key::A, Key::Left], &[Key::D, Key::Right]),
+    }
+}
+
+fn is_move(action: Action) -> bool {
+    matches!(action, Action::Forward | Action::Back | Action::StrafeLeft | Action::StrafeRight)
+}
+
+fn color_from_id(id: u64, team: Option<u8>) -> u32 {
+    // In team modes everyone wears their team's color
+    const TEAM_COLORS: [u32; 8] = [
+        0xff3c3c, 0x3c78ff, 0x3cdc3c, 0xffd23c, 0xc83cff, 0xff8c1e, 0x3cdcdc, 0xff78b4,
+    ];
+    if let Some(team) = team {
+        return TEAM_COLORS[team as usize % TEAM_COLORS.len()];
+    }
     // Deterministic bright color from id using HSV
     let hue = (id % 360) as f32; // 0..360
     let (r, g, b) = hsv_to_rgb(hue, 0.8, 0.95);
@@ -7,3 +7,4 @@ pub mod render {
     pub mod maze;
 }
 pub mod net;
+pub mod prediction;
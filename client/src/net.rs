@@ -1,80 +1,320 @@
 //! Networking helpers for the client (UDP Renet/Netcode).
-//! Provides a persistent NetClient for handshake, polling, and sending inputs.
+//! Provides a persistent NetClient for handshake, polling, and sending inputs. A dropped
+//! connection is re-established in the background and resumes the server-side session.
 
-use std::net::{UdpSocket, SocketAddr, ToSocketAddrs};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{Read, Write};
+use std::net::{TcpStream, UdpSocket, SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use renet::RenetClient;
-use renet_netcode::{ClientAuthentication, NetcodeClientTransport};
+use renet_netcode::{ClientAuthentication, ConnectToken, NetcodeClientTransport};
+use protocol::snapshot::SnapshotReceiver;
+use protocol::{Action, ClientMessage, Codec, ServerFrame, ServerMessage, Snapshot, Steering, PROTOCOL_VERSION};
+
+/// Delay before the first reconnect attempt; doubles per attempt up to `RECONNECT_MAX_DELAY`.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(8);
+/// Attempts before giving up; together they outlast the server's default session grace.
+pub const RECONNECT_ATTEMPTS: u32 = 8;
+
+/// Where the connection stands, for the UI to show.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Waiting for the first `Welcome`.
+    Connecting,
+    Connected,
+    /// The connection dropped; attempt `attempt` of `RECONNECT_ATTEMPTS` is pending or running.
+    Reconnecting { attempt: u32 },
+    /// Gone for good: refused, out of attempts, or left on purpose.
+    Disconnected(String),
+}
 
 pub struct NetClient {
     client: RenetClient,
     transport: NetcodeClientTransport,
+    /// When renet was last updated; it expects elapsed time, not wall-clock time.
+    last_update: Instant,
+    /// Codec for outgoing messages; JSON until the server's Welcome settles it.
+    codec: Codec,
+    snapshots: SnapshotReceiver,
+    /// Sequence number of the last input sent.
+    input_seq: u32,
+    server_addr: String,
+    username: String,
+    auth: Auth,
+    /// Token from the last `Welcome`, presented in `Hello` after a reconnect.
+    session: Option<u64>,
+    state: ConnectionState,
+    /// When the next reconnect attempt opens a new transport.
+    retry_at: Option<Instant>,
+    /// Newest simulation tick seen on a server frame.
+    server_tick: u64,
+}
+
+/// How the client proves who it is to netcode.
+#[derive(Debug, Clone)]
+pub enum Auth {
+    /// No token; only works against a server started without a private key.
+    Unsecure,
+    /// Connect token written by `server token`.
+    TokenFile(PathBuf),
+    /// TCP address of the server's token issuer (`server --issuer`).
+    Issuer(String),
+}
+
+impl Auth {
+    /// `MAZE_TOKEN=<file>` or `MAZE_TOKEN_ISSUER=<addr>`, unsecure when neither is set.
+    pub fn from_env() -> Self {
+        if let Ok(path) = std::env::var("MAZE_TOKEN") { return Auth::TokenFile(path.into()); }
+        if let Ok(addr) = std::env::var("MAZE_TOKEN_ISSUER") { return Auth::Issuer(addr); }
+        Auth::Unsecure
+    }
 }
 
 impl NetClient {
+    /// Connects using the authentication selected by [`Auth::from_env`].
     pub fn connect(server_addr: &str, username: &str) -> Option<Self> {
-        let client = RenetClient::new(renet::ConnectionConfig::default());
-        let server_addr = match resolve_server_addr(server_addr) {
-            Some(a) => a,
-            None => { eprintln!("Invalid server address"); return None; }
-        };
-
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Failed to bind UDP socket: {}", e);
-                return None;
-        }
-    };
-    let _ = socket.set_nonblocking(true);
+        Self::connect_with(server_addr, username, Auth::from_env())
+    }
 
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("time");
-    let auth = ClientAuthentication::Unsecure { protocol_id: 0, client_id: 0, server_addr, user_data: None };
-    let transport = match NetcodeClientTransport::new(now, auth, socket) {
-        Ok(t) => t,
-        Err(e) => {
-            eprintln!("Failed to create netcode client: {}", e);
-                return None;
-        }
-    };
+    /// With a token the server takes the player name from the token, not from `username`,
+    /// and dials the addresses inside it rather than `server_addr`.
+    pub fn connect_with(server_addr: &str, username: &str, auth: Auth) -> Option<Self> {
+        let (client, transport) = match open_transport(server_addr, username, &auth) {
+            Ok(t) => t,
+            Err(e) => { eprintln!("{}", e); return None; }
+        };
+        let mut nc = NetClient {
+            client,
+            transport,
+            last_update: Instant::now(),
+            codec: Codec::Json,
+            snapshots: SnapshotReceiver::default(),
+            input_seq: 0,
+            server_addr: server_addr.to_string(),
+            username: username.to_string(),
+            auth,
+            session: None,
+            state: ConnectionState::Connecting,
+            retry_at: None,
+            server_tick: 0,
+        };
+        nc.send_hello();
+        Some(nc)
+    }
 
-        let mut nc = NetClient { client, transport };
-        // Send Hello
-        let payload = serde_json::json!({
-            "event": "Hello",
-            "body": {"name": username},
+    /// Queues Hello (always JSON) asking for the preferred codec; it goes out once connected.
+    fn send_hello(&mut self) {
+        self.send(&ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            build: build_info(),
+            name: self.username.clone(),
+            codec: preferred_codec(),
+            resume: self.session,
         });
-        let bytes = serde_json::to_vec(&payload).unwrap();
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-    let _ = nc.transport.update(now, &mut nc.client);
-        nc.client
-            .send_message(renet::DefaultChannel::ReliableOrdered, bytes);
+    }
 
-        Some(nc)
+    pub fn state(&self) -> &ConnectionState {
+        &self.state
     }
 
-    pub fn poll(&mut self) -> Vec<serde_json::Value> {
+    /// Newest server tick received. Snapshots arrive unreliably, so this never goes back.
+    pub fn server_tick(&self) -> u64 {
+        self.server_tick
+    }
+
+    /// Drains pending server messages, each with the tick it was sent at. Snapshot deltas are
+    /// resolved and acked here, so every `ServerMessage::Snapshot` returned is a full one
+    /// (`baseline == None`).
+    /// Also drives reconnection; a resumed session starts over with `Welcome`, `Map` and
+    /// `PlayerInit` just like the first connect.
+    pub fn poll(&mut self) -> Vec<ServerFrame> {
         let mut out = Vec::new();
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        let _ = self.transport.update(now, &mut self.client);
-        while let Some(bytes) = self.client.receive_message(renet::DefaultChannel::ReliableOrdered) {
-            if let Ok(value) = serde_json::from_slice::<serde_json::Value>(&bytes) {
-                out.push(value);
+        if let Some(retry_at) = self.retry_at {
+            if Instant::now() < retry_at { return out; }
+            self.retry_at = None;
+            if let Err(e) = self.reopen() {
+                self.retry_later(&e);
+                return out;
+            }
+        }
+        if matches!(self.state, ConnectionState::Disconnected(_)) { return out; }
+
+        let dt = self.last_update.elapsed();
+        self.last_update = Instant::now();
+        self.client.update(dt);
+        let _ = self.transport.update(dt, &mut self.client);
+        for channel in [renet::DefaultChannel::ReliableOrdered, renet::DefaultChannel::Unreliable].map(u8::from) {
+            while let Some(bytes) = self.client.receive_message(channel) {
+                // Everything but `Rejected` arrives framed with the server tick
+                let decoded = ServerFrame::from_bytes(&bytes).or_else(|e| match ServerMessage::from_bytes(&bytes) {
+                    Ok(message @ ServerMessage::Rejected { .. }) => Ok(ServerFrame { tick: self.server_tick, message }),
+                    _ => Err(e),
+                });
+                let ServerFrame { tick, message } = match decoded {
+                    Ok(frame) => frame,
+                    Err(e) => { eprintln!("Dropping server message: {}", e); continue; }
+                };
+                self.server_tick = self.server_tick.max(tick);
+                match message {
+                    ServerMessage::Snapshot(snapshot) => {
+                        if let Some(table) = self.snapshots.receive(&snapshot) {
+                            let message = ServerMessage::Snapshot(Snapshot::full(snapshot.tick, table));
+                            out.push(ServerFrame { tick, message });
+                            let ack = ClientMessage::Ack { tick: snapshot.tick };
+                            self.client.send_message(renet::DefaultChannel::Unreliable, ack.to_bytes(self.codec));
+                        }
+                    }
+                    msg => {
+                        match &msg {
+                            ServerMessage::Welcome { codec, session, .. } => {
+                                self.codec = *codec;
+                                self.session = Some(*session);
+                                self.state = ConnectionState::Connected;
+                            }
+                            // The server is about to drop us anyway; leave cleanly
+                            ServerMessage::Rejected { reason } => {
+                                self.client.disconnect();
+                                self.state = ConnectionState::Disconnected(reason.clone());
+                            }
+                            _ => {}
+                        }
+                        out.push(ServerFrame { tick, message: msg });
+                    }
+                }
+            }
+        }
+        // Flush acks and anything queued since the last poll
+        let _ = self.transport.send_packets(&mut self.client);
+
+        if self.client.is_disconnected() {
+            let reason = self.disconnect_reason().unwrap_or_else(|| "connection lost".into());
+            match self.state {
+                // Only a welcomed client has a session worth resuming
+                ConnectionState::Connected => {
+                    eprintln!("Connection lost ({}); reconnecting", reason);
+                    self.state = ConnectionState::Reconnecting { attempt: 1 };
+                    self.retry_at = Some(Instant::now() + reconnect_delay(1));
+                }
+                ConnectionState::Reconnecting { .. } => self.retry_later(&reason),
+                ConnectionState::Connecting => self.state = ConnectionState::Disconnected(reason),
+                ConnectionState::Disconnected(_) => {}
             }
         }
         out
     }
 
-    pub fn send_input(&mut self, dx: i32, dy: i32) {
-        let msg = serde_json::json!({
-            "event": "Input",
-            "body": {"dx": dx, "dy": dy},
-        });
-        if let Ok(bytes) = serde_json::to_vec(&msg) {
-            self.client.send_message(renet::DefaultChannel::ReliableOrdered, bytes);
+    /// Replaces the dead transport with a fresh one and queues a resuming Hello.
+    fn reopen(&mut self) -> Result<(), String> {
+        let (client, transport) = open_transport(&self.server_addr, &self.username, &self.auth)?;
+        self.client = client;
+        self.transport = transport;
+        self.last_update = Instant::now();
+        self.codec = Codec::Json;
+        self.snapshots = SnapshotReceiver::default();
+        // The server may have restarted, with its tick back at zero
+        self.server_tick = 0;
+        self.send_hello();
+        Ok(())
+    }
+
+    /// Schedules the next reconnect attempt after a failed one, or gives up.
+    fn retry_later(&mut self, reason: &str) {
+        let ConnectionState::Reconnecting { attempt } = self.state else { return };
+        if attempt >= RECONNECT_ATTEMPTS {
+            self.state = ConnectionState::Disconnected(format!("gave up reconnecting: {}", reason));
+            return;
         }
+        eprintln!("Reconnect attempt {} failed ({})", attempt, reason);
+        self.state = ConnectionState::Reconnecting { attempt: attempt + 1 };
+        self.retry_at = Some(Instant::now() + reconnect_delay(attempt + 1));
+    }
+
+    /// Leaves the server right away so other players see us go instead of waiting for a timeout.
+    pub fn disconnect(&mut self) {
+        self.transport.disconnect();
+        self.retry_at = None;
+        self.state = ConnectionState::Disconnected("left the game".into());
+    }
+
+    /// Why the connection ended, if it has.
+    pub fn disconnect_reason(&self) -> Option<String> {
+        if let Some(reason) = self.transport.disconnect_reason() { return Some(reason.to_string()); }
+        self.client.disconnect_reason().map(|reason| reason.to_string())
+    }
+
+    /// Sends one action and returns its sequence number, which the server echoes back as
+    /// `PlayerState::last_input` once the action is processed.
+    pub fn send_input(&mut self, action: Action) -> u32 {
+        self.input_seq += 1;
+        self.send(&ClientMessage::Input { seq: self.input_seq, action });
+        self.input_seq
+    }
+
+    /// Sends the held movement keys in continuous mode; numbered like `send_input`.
+    pub fn send_steer(&mut self, steering: Steering) -> u32 {
+        self.input_seq += 1;
+        self.send(&ClientMessage::Steer { seq: self.input_seq, steering });
+        self.input_seq
+    }
+
+    pub fn send(&mut self, msg: &ClientMessage) {
+        self.client.send_message(renet::DefaultChannel::ReliableOrdered, msg.to_bytes(self.codec));
+    }
+}
+
+fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_BASE_DELAY.saturating_mul(1 << (attempt - 1).min(16)).min(RECONNECT_MAX_DELAY)
+}
+
+fn open_transport(server_addr: &str, username: &str, auth: &Auth) -> Result<(RenetClient, NetcodeClientTransport), String> {
+    let server_addr = resolve_server_addr(server_addr).ok_or("Invalid server address")?;
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to bind UDP socket: {}", e))?;
+    let _ = socket.set_nonblocking(true);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("time");
+    let auth = match auth {
+        Auth::Unsecure => {
+            // Unsecure netcode trusts the client id, so it only has to be unique per server
+            let client_id = now.as_nanos() as u64;
+            ClientAuthentication::Unsecure { protocol_id: PROTOCOL_VERSION.protocol_id(), client_id, server_addr, user_data: None }
+        }
+        Auth::TokenFile(path) => ClientAuthentication::Secure { connect_token: read_token_file(path)? },
+        // A fresh token per attempt; netcode refuses to reuse one from a new socket
+        Auth::Issuer(addr) => ClientAuthentication::Secure { connect_token: fetch_token(addr, username)? },
+    };
+    let transport = NetcodeClientTransport::new(now, auth, socket).map_err(|e| format!("Failed to create netcode client: {}", e))?;
+    Ok((RenetClient::new(renet::ConnectionConfig::default()), transport))
+}
+
+/// Sent in Hello so server logs show which client build connected.
+fn build_info() -> String {
+    format!("client {} ({})", env!("CARGO_PKG_VERSION"), std::env::consts::OS)
+}
+
+/// Binary unless `MAZE_CODEC=json` asks for readable traffic.
+fn preferred_codec() -> Codec {
+    std::env::var("MAZE_CODEC").ok().and_then(|c| Codec::parse(&c)).unwrap_or(Codec::Binary)
+}
+
+fn read_token_file(path: &std::path::Path) -> Result<ConnectToken, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Cannot open token file {}: {}", path.display(), e))?;
+    ConnectToken::read(&mut file).map_err(|e| format!("Invalid token file {}: {}", path.display(), e))
+}
+
+/// Asks the server's token issuer for a token bound to `username`.
+fn fetch_token(issuer: &str, username: &str) -> Result<ConnectToken, String> {
+    let mut stream = TcpStream::connect(issuer).map_err(|e| format!("Cannot reach token issuer {}: {}", issuer, e))?;
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let mut reply = Vec::new();
+    writeln!(stream, "{}", username)
+        .and_then(|_| stream.read_to_end(&mut reply))
+        .map_err(|e| format!("Token issuer {} failed: {}", issuer, e))?;
+    if let Some(reason) = reply.strip_prefix(b"ERR ") {
+        return Err(format!("Token issuer refused: {}", String::from_utf8_lossy(reason).trim()));
     }
+    ConnectToken::read(&mut reply.as_slice()).map_err(|e| format!("Invalid token from issuer {}: {}", issuer, e))
 }
 
 fn resolve_server_addr(input: &str) -> Option<SocketAddr> {
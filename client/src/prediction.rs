@@ -0,0 +1,58 @@
+//! Client-side prediction for the local player's movement.
+//! Inputs are applied immediately with the shared `protocol::movement` rules and kept until
+//! a snapshot shows the server processed them. Each snapshot rewinds to the authoritative
+//! pose and replays whatever is still unacknowledged.
+
+use std::collections::VecDeque;
+
+use protocol::movement::{self, Action, Pose};
+use protocol::PlayerState;
+
+/// Row-major maze as received in `ServerMessage::Map`.
+pub struct MapView<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub cells: &'a [u8],
+}
+
+#[derive(Debug, Default)]
+pub struct Prediction {
+    /// Predicted pose; `None` until the server placed us.
+    pose: Option<Pose>,
+    /// Inputs sent but not yet reflected in a snapshot, oldest first.
+    pending: VecDeque<(u32, Action)>,
+}
+
+impl Prediction {
+    pub fn pose(&self) -> Option<Pose> {
+        self.pose
+    }
+
+    /// Starts over from a fresh spawn (`PlayerInit`).
+    pub fn reset(&mut self, spawn: PlayerState) {
+        self.pose = Some(spawn.pose());
+        self.pending.clear();
+    }
+
+    /// Applies input `seq` locally right after it was sent.
+    pub fn input(&mut self, seq: u32, action: Action, map: &MapView) {
+        let Some(pose) = self.pose else { return };
+        self.pose = Some(movement::apply(map.cells, map.width, map.height, pose, action));
+        self.pending.push_back((seq, action));
+    }
+
+    /// Reconciles with our own entry from a snapshot. Returns true when the prediction was
+    /// wrong, e.g. the map changed under us before the server saw the input.
+    pub fn reconcile(&mut self, server: PlayerState, map: &MapView) -> bool {
+        while self.pending.front().is_some_and(|(seq, _)| *seq <= server.last_input) {
+            self.pending.pop_front();
+        }
+        let mut pose = server.pose();
+        for (_, action) in &self.pending {
+            pose = movement::apply(map.cells, map.width, map.height, pose, *action);
+        }
+        let mispredicted = self.pose != Some(pose);
+        self.pose = Some(pose);
+        mispredicted
+    }
+}
@@ -19,9 +19,13 @@ impl FpsCounter {
     }
 }
 
-// -------- Tiny bitmap text for on-screen FPS overlay ---------
+impl Default for FpsCounter {
+    fn default() -> Self { Self::new() }
+}
+
+// -------- Tiny bitmap text for on-screen overlays ---------
 
-// 5x7 bitmap font for a small subset: digits and 'F','P','S', and ':'
+// 5x7 bitmap font: digits, uppercase letters and common punctuation.
 // Each glyph is 7 rows of 5 bits (MSB left), packed into u8 (lower 5 bits used per row)
 fn glyph_bits(ch: char) -> Option<[u8; 7]> {
     match ch {
@@ -35,9 +39,54 @@ fn glyph_bits(ch: char) -> Option<[u8; 7]> {
         '7' => Some([0b11111,0b00001,0b00010,0b00100,0b01000,0b01000,0b01000]),
         '8' => Some([0b01110,0b10001,0b10001,0b01110,0b10001,0b10001,0b01110]),
         '9' => Some([0b01110,0b10001,0b10001,0b01111,0b00001,0b00010,0b01100]),
+        'A' => Some([0b01110,0b10001,0b10001,0b11111,0b10001,0b10001,0b10001]),
+        'B' => Some([0b11110,0b10001,0b10001,0b11110,0b10001,0b10001,0b11110]),
+        'C' => Some([0b01110,0b10001,0b10000,0b10000,0b10000,0b10001,0b01110]),
+        'D' => Some([0b11100,0b10010,0b10001,0b10001,0b10001,0b10010,0b11100]),
+        'E' => Some([0b11111,0b10000,0b10000,0b11110,0b10000,0b10000,0b11111]),
         'F' => Some([0b11111,0b10000,0b11110,0b10000,0b10000,0b10000,0b10000]),
+        'G' => Some([0b01110,0b10001,0b10000,0b10111,0b10001,0b10001,0b01111]),
+        'H' => Some([0b10001,0b10001,0b10001,0b11111,0b10001,0b10001,0b10001]),
+        'I' => Some([0b01110,0b00100,0b00100,0b00100,0b00100,0b00100,0b01110]),
+        'J' => Some([0b00111,0b00010,0b00010,0b00010,0b00010,0b10010,0b01100]),
+        'K' => Some([0b10001,0b10010,0b10100,0b11000,0b10100,0b10010,0b10001]),
+        'L' => Some([0b10000,0b10000,0b10000,0b10000,0b10000,0b10000,0b11111]),
+        'M' => Some([0b10001,0b11011,0b10101,0b10101,0b10001,0b10001,0b10001]),
+        'N' => Some([0b10001,0b10001,0b11001,0b10101,0b10011,0b10001,0b10001]),
+        'O' => Some([0b01110,0b10001,0b10001,0b10001,0b10001,0b10001,0b01110]),
         'P' => Some([0b11110,0b10001,0b10001,0b11110,0b10000,0b10000,0b10000]),
+        'Q' => Some([0b01110,0b10001,0b10001,0b10001,0b10101,0b10010,0b01101]),
+        'R' => Some([0b11110,0b10001,0b10001,0b11110,0b10100,0b10010,0b10001]),
         'S' => Some([0b01111,0b10000,0b10000,0b01110,0b00001,0b00001,0b11110]),
+        'T' => Some([0b11111,0b00100,0b00100,0b00100,0b00100,0b00100,0b00100]),
+        'U' => Some([0b10001,0b10001,0b10001,0b10001,0b10001,0b10001,0b01110]),
+        'V' => Some([0b10001,0b10001,0b10001,0b10001,0b10001,0b01010,0b00100]),
+        'W' => Some([0b10001,0b10001,0b10001,0b10101,0b10101,0b10101,0b01010]),
+        'X' => Some([0b10001,0b10001,0b01010,0b00100,0b01010,0b10001,0b10001]),
+        'Y' => Some([0b10001,0b10001,0b10001,0b01010,0b00100,0b00100,0b00100]),
+        'Z' => Some([0b11111,0b00001,0b00010,0b00100,0b01000,0b10000,0b11111]),
+        '.' => Some([0b00000,0b00000,0b00000,0b00000,0b00000,0b01100,0b01100]),
+        ',' => Some([0b00000,0b00000,0b00000,0b00000,0b01100,0b00100,0b01000]),
+        ';' => Some([0b00000,0b01100,0b01100,0b00000,0b01100,0b00100,0b01000]),
+        '-' => Some([0b00000,0b00000,0b00000,0b11111,0b00000,0b00000,0b00000]),
+        '+' => Some([0b00000,0b00100,0b00100,0b11111,0b00100,0b00100,0b00000]),
+        '=' => Some([0b00000,0b00000,0b11111,0b00000,0b11111,0b00000,0b00000]),
+        '_' => Some([0b00000,0b00000,0b00000,0b00000,0b00000,0b00000,0b11111]),
+        '/' => Some([0b00000,0b00001,0b00010,0b00100,0b01000,0b10000,0b00000]),
+        '!' => Some([0b00100,0b00100,0b00100,0b00100,0b00100,0b00000,0b00100]),
+        '?' => Some([0b01110,0b10001,0b00001,0b00010,0b00100,0b00000,0b00100]),
+        '(' => Some([0b00010,0b00100,0b01000,0b01000,0b01000,0b00100,0b00010]),
+        ')' => Some([0b01000,0b00100,0b00010,0b00010,0b00010,0b00100,0b01000]),
+        '[' => Some([0b01110,0b01000,0b01000,0b01000,0b01000,0b01000,0b01110]),
+        ']' => Some([0b01110,0b00010,0b00010,0b00010,0b00010,0b00010,0b01110]),
+        '<' => Some([0b00010,0b00100,0b01000,0b10000,0b01000,0b00100,0b00010]),
+        '>' => Some([0b01000,0b00100,0b00010,0b00001,0b00010,0b00100,0b01000]),
+        '\'' => Some([0b01100,0b00100,0b01000,0b00000,0b00000,0b00000,0b00000]),
+        '"' => Some([0b01010,0b01010,0b01010,0b00000,0b00000,0b00000,0b00000]),
+        '#' => Some([0b01010,0b01010,0b11111,0b01010,0b11111,0b01010,0b01010]),
+        '%' => Some([0b11000,0b11001,0b00010,0b00100,0b01000,0b10011,0b00011]),
+        '*' => Some([0b00000,0b00100,0b10101,0b01110,0b10101,0b00100,0b00000]),
+        '@' => Some([0b01110,0b10001,0b00001,0b01101,0b10101,0b10101,0b01110]),
         ':' => Some([0b00000,0b00100,0b00100,0b00000,0b00100,0b00100,0b00000]),
         ' ' => Some([0b00000,0b00000,0b00000,0b00000,0b00000,0b00000,0b00000]),
         _ => None,
@@ -48,7 +97,8 @@ pub fn draw_text(buf: &mut [u32], w: usize, x: usize, y: usize, text: &str, fg:
     let mut pen_x = x as isize;
     let y = y as isize;
     for ch in text.chars() {
-        if let Some(bits) = glyph_bits(ch) {
+        // Lowercase renders with the uppercase glyphs
+        if let Some(bits) = glyph_bits(ch.to_ascii_uppercase()) {
             // optional background block
             if let Some(bgcol) = bg {
                 for ry in 0..7 {
@@ -80,3 +130,8 @@ pub fn draw_text(buf: &mut [u32], w: usize, x: usize, y: usize, text: &str, fg:
         }
     }
 }
+
+/// Width in pixels of `text` as drawn by [`draw_text`].
+pub fn text_width(text: &str) -> usize {
+    text.chars().map(|ch| if glyph_bits(ch.to_ascii_uppercase()).is_some() { 6 } else { 4 }).sum()
+}
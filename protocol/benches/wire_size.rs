@@ -0,0 +1,64 @@
+//! Compares JSON and binary frame sizes for the two heaviest messages.
+//! Run with `cargo bench -p protocol`.
+
+use std::time::Instant;
+
+use protocol::snapshot::PlayerTable;
+use protocol::{Codec, Facing, PlayerState, ServerMessage, Snapshot};
+
+const ITERATIONS: u32 = 10_000;
+
+fn main() {
+    // Same dimensions as the server's default maze; walls on even rows/cols like a fresh grid
+    let (width, height) = (41usize, 31usize);
+    let cells: Vec<u8> = (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            u8::from(x % 2 == 0 || y % 2 == 0)
+        })
+        .collect();
+    let map = ServerMessage::Map { width, height, cells, exit: None, seed: Some(0), dead_ends: 20 };
+
+    // 32 players (server max_clients) spread over the grid with realistic client ids
+    let table: PlayerTable = (0..32u64)
+        .map(|i| PlayerState { id: 1_700_000_000_000 + i * 7919, x: (i as usize * 3) % width, y: (i as usize * 5) % height, facing: Facing::ALL[i as usize % 4], last_input: 0, team: None, motion: None })
+        .map(|p| (p.id, p))
+        .collect();
+    let full = ServerMessage::Snapshot(Snapshot::full(100, &table));
+
+    // Typical steady state: one player stepped since the acked tick
+    let mut moved = table.clone();
+    if let Some(p) = moved.values_mut().next() { p.x += 1; }
+    let delta = ServerMessage::Snapshot(Snapshot::delta(101, &moved, 100, &table));
+
+    println!("{:<22} {:>8} {:>8} {:>7} {:>12} {:>12}", "message", "json B", "bin B", "ratio", "json enc+dec", "bin enc+dec");
+    report("Map 41x31", &map);
+    report("Snapshot x32 full", &full);
+    report("Snapshot x32 1 moved", &delta);
+}
+
+fn report(name: &str, msg: &ServerMessage) {
+    let json = msg.to_bytes(Codec::Json);
+    let binary = msg.to_bytes(Codec::Binary);
+    assert_eq!(&ServerMessage::from_bytes(&binary).expect("binary round trip"), msg);
+    assert_eq!(&ServerMessage::from_bytes(&json).expect("json round trip"), msg);
+
+    println!(
+        "{:<22} {:>8} {:>8} {:>6.1}x {:>10.2}us {:>10.2}us",
+        name,
+        json.len(),
+        binary.len(),
+        json.len() as f64 / binary.len() as f64,
+        round_trip_micros(msg, Codec::Json),
+        round_trip_micros(msg, Codec::Binary),
+    );
+}
+
+fn round_trip_micros(msg: &ServerMessage, codec: Codec) -> f64 {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let bytes = std::hint::black_box(msg.to_bytes(codec));
+        let _ = std::hint::black_box(ServerMessage::from_bytes(&bytes));
+    }
+    start.elapsed().as_secs_f64() * 1e6 / ITERATIONS as f64
+}
@@ -0,0 +1,195 @@
+//! Maze metrics: how many dead ends and loops a `map_cells` grid has, how far apart things
+//! are and how branchy it is. Used to check that harder difficulties really are harder, and
+//! logged by the server on every level change.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Metrics of one maze; every distance is in steps between neighbouring open cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MazeStats {
+    pub open_cells: usize,
+    /// Open cells with a single open neighbour.
+    pub dead_ends: usize,
+    /// Independent cycles (edges - cells + components); zero for a perfect maze.
+    pub loops: usize,
+    /// Longest shortest path between any two open cells, i.e. the maze's diameter.
+    pub longest_path: usize,
+    /// Mean length of the corridors between junctions and dead ends.
+    pub avg_corridor: f32,
+    /// Open cells with three or more open neighbours.
+    pub junctions: usize,
+    /// Mean number of ways on from a junction, not counting the way in.
+    pub branching_factor: f32,
+    /// Shortest path between every pair of spawns, ascending; unreachable pairs are left out.
+    pub spawn_distances: Vec<usize>,
+}
+
+impl MazeStats {
+    /// Analyses a row-major `width` x `height` grid, `0` passage / `1` wall, with players
+    /// spawning at `spawns`.
+    pub fn analyse(cells: &[u8], width: usize, height: usize, spawns: &[(usize, usize)]) -> Self {
+        let grid = Grid { cells, width, height };
+        let open: Vec<usize> = (0..width * height).filter(|i| grid.is_open(*i)).collect();
+        let degree = |i: usize| grid.neighbours(i).count();
+
+        let dead_ends = open.iter().filter(|i| degree(**i) == 1).count();
+        let junction_degrees: Vec<usize> = open.iter().map(|i| degree(*i)).filter(|d| *d >= 3).collect();
+        let edges: usize = open.iter().map(|i| degree(*i)).sum::<usize>() / 2;
+        let components = grid.components(&open);
+        let loops = (edges + components).saturating_sub(open.len());
+
+        let longest_path = grid.diameter(&open);
+
+        let mut spawn_distances = Vec::new();
+        for (n, a) in spawns.iter().enumerate() {
+            let from = grid.distances(a.1 * width + a.0);
+            spawn_distances.extend(spawns[n + 1..].iter().filter_map(|b| from.get(b.1 * width + b.0).copied().flatten()));
+        }
+        spawn_distances.sort_unstable();
+
+        let corridors = grid.corridors(&open);
+        Self {
+            open_cells: open.len(),
+            dead_ends,
+            loops,
+            longest_path,
+            avg_corridor: mean(corridors.iter().copied()),
+            junctions: junction_degrees.len(),
+            branching_factor: mean(junction_degrees.iter().map(|d| d - 1)),
+            spawn_distances,
+        }
+    }
+}
+
+impl fmt::Display for MazeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} open cells, {} dead ends, {} loops, longest path {}, corridors {:.1}, {} junctions x{:.2}",
+            self.open_cells, self.dead_ends, self.loops, self.longest_path, self.avg_corridor, self.junctions, self.branching_factor
+        )?;
+        if let (Some(min), Some(max)) = (self.spawn_distances.first(), self.spawn_distances.last()) {
+            write!(f, ", spawns {min}..{max} apart (mean {:.1})", mean(self.spawn_distances.iter().copied()))?;
+        }
+        Ok(())
+    }
+}
+
+fn mean(values: impl Iterator<Item = usize>) -> f32 {
+    let (sum, count) = values.fold((0, 0), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 { 0.0 } else { sum as f32 / count as f32 }
+}
+
+struct Grid<'a> {
+    cells: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl Grid<'_> {
+    fn is_open(&self, i: usize) -> bool {
+        self.cells.get(i) == Some(&0)
+    }
+
+    fn neighbours(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        let (x, y) = (i % self.width, i / self.width);
+        [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)]
+            .into_iter()
+            .filter(|(x, y)| *x < self.width && *y < self.height)
+            .map(|(x, y)| y * self.width + x)
+            .filter(|i| self.is_open(*i))
+    }
+
+    /// Breadth-first distances from `from`; `None` for walls and unreachable cells.
+    fn distances(&self, from: usize) -> Vec<Option<usize>> {
+        let mut dist = vec![None; self.width * self.height];
+        if !self.is_open(from) { return dist; }
+        dist[from] = Some(0);
+        let mut queue = VecDeque::from([from]);
+        while let Some(i) = queue.pop_front() {
+            let d = dist[i].unwrap_or(0);
+            for n in self.neighbours(i) {
+                if dist[n].is_none() {
+                    dist[n] = Some(d + 1);
+                    queue.push_back(n);
+                }
+            }
+        }
+        dist
+    }
+
+    /// Longest shortest path, by a breadth-first search from every open cell over a compact
+    /// adjacency list; exact for mazes with loops too.
+    fn diameter(&self, open: &[usize]) -> usize {
+        let mut compact = vec![u32::MAX; self.width * self.height];
+        for (n, i) in open.iter().enumerate() {
+            compact[*i] = n as u32;
+        }
+        let adjacency: Vec<Vec<u32>> = open.iter().map(|i| self.neighbours(*i).map(|n| compact[n]).collect()).collect();
+        let mut dist = vec![u32::MAX; open.len()];
+        let mut queue: Vec<u32> = Vec::with_capacity(open.len());
+        let mut longest = 0;
+        for start in 0..open.len() as u32 {
+            dist.fill(u32::MAX);
+            queue.clear();
+            dist[start as usize] = 0;
+            queue.push(start);
+            let mut head = 0;
+            while let Some(&i) = queue.get(head) {
+                head += 1;
+                let d = dist[i as usize] + 1;
+                for &n in &adjacency[i as usize] {
+                    if dist[n as usize] == u32::MAX {
+                        dist[n as usize] = d;
+                        longest = longest.max(d);
+                        queue.push(n);
+                    }
+                }
+            }
+        }
+        longest as usize
+    }
+
+    fn components(&self, open: &[usize]) -> usize {
+        let mut seen = vec![false; self.width * self.height];
+        let mut count = 0;
+        for &start in open {
+            if seen[start] { continue; }
+            count += 1;
+            seen[start] = true;
+            let mut stack = vec![start];
+            while let Some(i) = stack.pop() {
+                for n in self.neighbours(i) {
+                    if !seen[n] {
+                        seen[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// Lengths of the corridors joining cells that are not simple passages (dead ends and
+    /// junctions). Each corridor is walked from both ends, so only the walk from the lower
+    /// end cell is kept.
+    fn corridors(&self, open: &[usize]) -> Vec<usize> {
+        let is_node = |i: usize| self.neighbours(i).count() != 2;
+        let mut lengths = Vec::new();
+        for &start in open.iter().filter(|i| is_node(**i)) {
+            for first in self.neighbours(start) {
+                let (mut prev, mut at, mut len) = (start, first, 1);
+                while !is_node(at) {
+                    let Some(next) = self.neighbours(at).find(|n| *n != prev) else { break };
+                    (prev, at, len) = (at, next, len + 1);
+                    if at == start { break; }
+                }
+                if start < at || (start == at && first < prev) {
+                    lengths.push(len);
+                }
+            }
+        }
+        lengths
+    }
+}
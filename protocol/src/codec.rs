@@ -0,0 +1,104 @@
+//! Wire encodings for protocol messages.
+//! JSON stays available for debugging; the binary codec (postcard) is negotiated in `Hello`
+//! and packs maze cells into bits. Decoding detects the codec from the first byte, so a
+//! receiver never needs to know which one the sender picked.
+
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// First byte of every binary frame. JSON frames start with `{` or, for unit variants like
+/// `Shoot`, `"`; being UTF-8, they can never start with this continuation byte.
+const BINARY_MARKER: u8 = 0xB1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    #[default]
+    Json,
+    Binary,
+}
+
+impl Codec {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Codec::Json => "json",
+            Codec::Binary => "binary",
+        }
+    }
+
+    /// Parses a lowercase codec name, e.g. from `MAZE_CODEC`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Codec::Json),
+            "binary" => Some(Codec::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, msg: &T) -> Vec<u8> {
+        match self {
+            Codec::Json => serde_json::to_vec(msg).expect("message serializes to json"),
+            Codec::Binary => postcard::to_extend(msg, vec![BINARY_MARKER]).expect("message serializes to binary"),
+        }
+    }
+
+    /// Decodes a frame produced by [`Codec::encode`] with either codec.
+    pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+        match bytes.first() {
+            None => Err(DecodeError::Empty),
+            Some(&BINARY_MARKER) => postcard::from_bytes(&bytes[1..]).map_err(DecodeError::Binary),
+            Some(_) => serde_json::from_slice(bytes).map_err(DecodeError::Json),
+        }
+    }
+}
+
+/// Error returned when incoming bytes are not a valid message.
+#[derive(Debug)]
+pub enum DecodeError {
+    Empty,
+    Json(serde_json::Error),
+    Binary(postcard::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Empty => write!(f, "empty message"),
+            DecodeError::Json(e) => write!(f, "malformed json message: {}", e),
+            DecodeError::Binary(e) => write!(f, "malformed binary message: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// `serde(with)` helper for wall grids: a plain `0`/`1` array in JSON, one bit per cell
+/// in binary. Any non-zero cell is sent as a wall.
+pub(crate) mod packed_cells {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(cells: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            return cells.serialize(s);
+        }
+        let mut bits = vec![0u8; cells.len().div_ceil(8)];
+        for (i, &c) in cells.iter().enumerate() {
+            if c != 0 { bits[i / 8] |= 1 << (i % 8); }
+        }
+        (cells.len() as u32, bits).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        if d.is_human_readable() {
+            return Vec::<u8>::deserialize(d);
+        }
+        let (len, bits) = <(u32, Vec<u8>)>::deserialize(d)?;
+        let len = len as usize;
+        if bits.len() != len.div_ceil(8) {
+            return Err(serde::de::Error::custom("packed cell count does not match payload"));
+        }
+        Ok((0..len).map(|i| (bits[i / 8] >> (i % 8)) & 1).collect())
+    }
+}
@@ -0,0 +1,336 @@
+//! Wire protocol shared by the MazeWars server and clients.
+//! Every message is a typed enum variant, so a protocol change breaks the build on both
+//! sides instead of silently defaulting fields on the receiving end.
+
+mod codec;
+pub mod analysis;
+pub mod maze;
+pub mod movement;
+pub mod rate;
+pub mod snapshot;
+
+use serde::{Deserialize, Serialize};
+
+pub use codec::{Codec, DecodeError};
+pub use movement::{Action, Facing, Motion, MovementMode, Steering};
+pub use snapshot::Snapshot;
+
+/// Version of this protocol. Bump `minor` for any message change; bump `major` only when
+/// `Hello` or `Rejected` themselves change shape.
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion { major: 1, minor: 16 };
+
+/// "MAZE" in the upper bytes keeps us clear of other netcode games on the same port.
+const PROTOCOL_MAGIC: u64 = 0x4D41_5A45;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ProtocolVersion {
+    /// Netcode `protocol_id`. Derived from the major version only, so a client that differs
+    /// in `minor` still reaches the `Hello` check and gets an explicit `Rejected`.
+    pub const fn protocol_id(self) -> u64 {
+        (PROTOCOL_MAGIC << 32) | self.major as u64
+    }
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Messages sent by a client to the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ClientMessage {
+    /// First message after connecting; registers the player under `name`.
+    /// `codec` is the encoding the client would like the server to use from now on.
+    /// Always sent as JSON so any server build can read `version`.
+    Hello {
+        version: ProtocolVersion,
+        /// Free-form client build description, logged by the server.
+        build: String,
+        name: String,
+        #[serde(default)]
+        codec: Codec,
+        /// Session token from an earlier `Welcome`; reclaims that player if it is still parked.
+        /// Left out of the JSON when `None`, so a first `Hello` still decodes on servers from
+        /// before sessions, which then answer with `Rejected` as usual. Only a server that
+        /// handed out the token sees it again, and that server knows the field; this is why
+        /// adding it needed no `major` bump.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resume: Option<u64>,
+    },
+    /// One movement or turn command. `seq` increases by one per input so the server can
+    /// report which inputs it has applied.
+    Input { seq: u32, action: Action },
+    /// Held movement keys in `MovementMode::Continuous`; the server applies the latest one on
+    /// every tick until the next arrives. `seq` is echoed like `Input`'s.
+    Steer { seq: u32, steering: Steering },
+    /// Latest snapshot tick the client resolved; sent on the unreliable channel.
+    Ack { tick: u64 },
+    /// Fire along the current facing. Ignored while the previous shot is cooling down.
+    Shoot,
+    /// Asks for the results of recent rounds; answered with `History`.
+    QueryHistory,
+    /// Marks the player ready (or not) to start the next round. Only counts in `Lobby`.
+    Ready { ready: bool },
+    /// Votes for the next level's difficulty; `Lobby` only, a later vote replaces an earlier one.
+    VoteDifficulty { difficulty: Difficulty },
+    /// Votes for the next level's maze size; `Lobby` only.
+    VoteMapSize { size: MapSize },
+    /// Votes for the next level by its playlist `number`; `Lobby` only, and only counted
+    /// when the server's playlist advances by vote.
+    VoteLevel { level: u32 },
+    /// A chat line, at most `MAX_CHAT_LEN` characters. `team_only` keeps it to the sender's
+    /// team. Lines starting with `/` are commands run by the server (`/help` lists them).
+    Chat { text: String, team_only: bool },
+}
+
+/// Messages sent by the server to a client. `Snapshot` travels on the unreliable channel,
+/// everything else on the reliable ordered one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ServerMessage {
+    /// Reply to `Hello`, carrying the id the server assigned to this client and the codec
+    /// both sides use for the rest of the session. `session` is a fresh token for resuming
+    /// after a dropped connection; `resumed` says whether the `Hello` reclaimed a player.
+    /// `tick_hz` is the rate of the tick stamped on every `ServerFrame`.
+    /// `movement` tells the client whether to send `Input` or `Steer`.
+    Welcome { message: String, client_id: u64, codec: Codec, session: u64, resumed: bool, tick_hz: u32, movement: MovementMode },
+    /// Reply to `Hello` when the server will not accept the client; always sent as JSON.
+    /// The server drops the connection shortly after.
+    Rejected { reason: String },
+    /// Full maze snapshot, row-major, `0` passage / `1` wall. `exit` is the goal cell when
+    /// the server plays with a reach-the-exit win condition. For a generated maze,
+    /// `maze::generate_with(seed, width, height, dead_ends, algorithm)`, with `algorithm` from
+    /// `Round`, rebuilds exactly these cells; a handcrafted map has no `seed` and can only be
+    /// taken as sent.
+    Map {
+        width: usize,
+        height: usize,
+        #[serde(with = "codec::packed_cells")]
+        cells: Vec<u8>,
+        exit: Option<(usize, usize)>,
+        seed: Option<u64>,
+        /// Percent of dead ends opened into loops; `0` for a handcrafted map.
+        dead_ends: usize,
+    },
+    /// The receiving client's own player and spawn position.
+    PlayerInit(PlayerState),
+    /// Fixed-rate player state, delta-encoded against the client's last acked tick.
+    Snapshot(Snapshot),
+    /// A player entered the game; sent to everyone, the new player included.
+    PlayerJoined { id: u64, name: String },
+    /// A player's connection ended and they were removed from the world.
+    PlayerLeft { id: u64, reason: String },
+    /// A shot traced from cell `from` to cell `to` (the hit player or the last open cell
+    /// before a wall). Sent to clients that can see the shooter or the end of the tracer.
+    ShotFired { shooter: u64, from: (usize, usize), to: (usize, usize) },
+    /// `shooter`'s shot hit `target`, leaving it `health`; sent to everyone.
+    PlayerHit { shooter: u64, target: u64, health: u32 },
+    /// `victim` ran out of health. It leaves snapshots until it respawns, when its client
+    /// gets a fresh `PlayerInit`.
+    PlayerKilled { killer: u64, victim: u64 },
+    /// Standings for the current round, best score first. Sent whenever they change and
+    /// again at `Intermission`. `teams` is empty in free-for-all.
+    Scoreboard { entries: Vec<ScoreEntry>, teams: Vec<TeamScore> },
+    /// How the round that just ended went; sent to everyone on the switch to `Intermission`.
+    RoundResult(RoundResult),
+    /// Reply to `QueryHistory`: the most recent rounds, oldest first.
+    History { rounds: Vec<RoundResult> },
+    /// Ready players and vote tallies while in `Lobby`, sent whenever they change. The round
+    /// starts once `needed` players are ready, or when the lobby times out.
+    LobbyStatus {
+        ready: Vec<u64>,
+        needed: u32,
+        difficulty_votes: Vec<(Difficulty, u32)>,
+        size_votes: Vec<(MapSize, u32)>,
+        /// Votes per playlist level number; empty unless the playlist advances by vote.
+        level_votes: Vec<(u32, u32)>,
+    },
+    /// Round state transition. The new state lasts `remaining_ms` from the frame's tick.
+    /// `algorithm` generated the current maze; `level` is the playlist entry being played,
+    /// `None` on servers without a playlist.
+    Round { state: RoundState, difficulty: Difficulty, algorithm: maze::Algorithm, level: Option<LevelInfo>, remaining_ms: u32 },
+    /// A chat line from player `from`, or a reply to a command when `from` is `None`.
+    /// `sent_at_ms` is the server's wall clock in milliseconds since the Unix epoch.
+    Chat { from: Option<u64>, name: String, text: String, team_only: bool, sent_at_ms: u64 },
+}
+
+/// A server message stamped with the server's simulation tick. Everything the server sends
+/// is framed, except `Rejected`, which stays a bare JSON `ServerMessage` so clients of any
+/// version can read it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerFrame {
+    pub tick: u64,
+    pub message: ServerMessage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlayerState {
+    pub id: u64,
+    pub x: usize,
+    pub y: usize,
+    pub facing: Facing,
+    /// `seq` of the last `Input` from this player the server processed; `0` before any.
+    pub last_input: u32,
+    /// Team in team modes, `None` in free-for-all.
+    pub team: Option<u8>,
+    /// Exact position and yaw in `MovementMode::Continuous`; `x`, `y` and `facing` are then
+    /// the cell and compass direction it rounds to.
+    pub motion: Option<Motion>,
+}
+
+impl PlayerState {
+    pub fn pose(&self) -> movement::Pose {
+        movement::Pose { x: self.x, y: self.y, facing: self.facing }
+    }
+}
+
+/// Longest chat line the server accepts, in characters; longer ones are cut.
+pub const MAX_CHAT_LEN: usize = 160;
+
+/// Hits a player takes to die; health starts here at every spawn.
+pub const MAX_HEALTH: u32 = 3;
+
+/// One player's line on the scoreboard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScoreEntry {
+    pub id: u64,
+    pub name: String,
+    pub team: Option<u8>,
+    pub kills: u32,
+    pub deaths: u32,
+    pub score: i32,
+}
+
+/// One team's aggregated line on the scoreboard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeamScore {
+    pub team: u8,
+    pub players: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub score: i32,
+}
+
+/// Outcome of one round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoundResult {
+    /// Counts up from 1 for the server's lifetime.
+    pub round: u32,
+    /// Player who won or clinched the round; `None` on a draw, e.g. a tie on score when
+    /// time ran out, or when a whole team won.
+    pub winner: Option<u64>,
+    /// Winning team in team modes.
+    pub team: Option<u8>,
+    pub reason: RoundEnd,
+    /// Final standings, best first.
+    pub scores: Vec<ScoreEntry>,
+    /// Final team standings, best first; empty in free-for-all.
+    pub teams: Vec<TeamScore>,
+    pub duration_ms: u32,
+    pub difficulty: Difficulty,
+}
+
+/// Which end condition finished a round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundEnd { ScoreLimit, LastStanding, ReachedExit, TimeLimit }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundState { Lobby, InRound, Intermission }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty { Easy, Medium, Hard }
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    /// Parses a lowercase difficulty name, e.g. from `MAZE_DIFFICULTY`.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.as_str() == name)
+    }
+}
+
+/// A level of the server's playlist, as announced in `Round`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelInfo {
+    /// Position in the playlist, counting from 1.
+    pub number: u32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MapSize { Small, #[default] Medium, Large }
+
+impl MapSize {
+    pub const ALL: [MapSize; 3] = [MapSize::Small, MapSize::Medium, MapSize::Large];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MapSize::Small => "small",
+            MapSize::Medium => "medium",
+            MapSize::Large => "large",
+        }
+    }
+
+    /// Maze width and height in cells; always odd, as the maze generator needs.
+    pub fn dimensions(self) -> (usize, usize) {
+        match self {
+            MapSize::Small => (31, 21),
+            MapSize::Medium => (41, 31),
+            MapSize::Large => (61, 45),
+        }
+    }
+}
+
+impl ClientMessage {
+    pub fn to_bytes(&self, codec: Codec) -> Vec<u8> {
+        codec.encode(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Codec::decode(bytes)
+    }
+}
+
+impl ServerMessage {
+    pub fn to_bytes(&self, codec: Codec) -> Vec<u8> {
+        codec.encode(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Codec::decode(bytes)
+    }
+}
+
+impl ServerFrame {
+    pub fn to_bytes(&self, codec: Codec) -> Vec<u8> {
+        codec.encode(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Codec::decode(bytes)
+    }
+}
@@ -0,0 +1,443 @@
+//! Seeded maze generation with pluggable algorithms. The cells depend only on the seed, the
+//! size, the difficulty and the algorithm: the random source is a fixed SplitMix64 and
+//! nothing goes through floating point, so every machine, server and client alike, builds
+//! byte-identical mazes from the same inputs.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Difficulty;
+
+/// SplitMix64: small, fast and fully specified, unlike `rand`'s generators which may change
+/// between releases.
+#[derive(Debug, Clone)]
+pub struct MazeRng(u64);
+
+impl MazeRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-enough index in `0..n`; `n` must not be zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// Maze generation algorithm. All of them fill the same odd-sized grid: passages on odd
+/// coordinates, the walls between them on even ones, and a closed outer border.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    /// Recursive backtracker: long winding corridors, few branches.
+    #[default]
+    Backtracker,
+    /// Randomized Prim: many short dead ends branching off everywhere.
+    Prim,
+    /// Randomized Kruskal: evenly spread, medium-length branches.
+    Kruskal,
+    /// Wilson's algorithm: a uniform spanning tree, free of any texture bias.
+    Wilson,
+    /// Eller's algorithm: built row by row, with mostly horizontal runs.
+    Eller,
+    /// Binary tree: every cell links north or west, leaving two open borders and a diagonal bias.
+    BinaryTree,
+    /// Open rectangular rooms dug into a backtracker maze.
+    Rooms,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 7] = [
+        Algorithm::Backtracker,
+        Algorithm::Prim,
+        Algorithm::Kruskal,
+        Algorithm::Wilson,
+        Algorithm::Eller,
+        Algorithm::BinaryTree,
+        Algorithm::Rooms,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Algorithm::Backtracker => "backtracker",
+            Algorithm::Prim => "prim",
+            Algorithm::Kruskal => "kruskal",
+            Algorithm::Wilson => "wilson",
+            Algorithm::Eller => "eller",
+            Algorithm::BinaryTree => "binarytree",
+            Algorithm::Rooms => "rooms",
+        }
+    }
+
+    /// Parses a lowercase algorithm name, e.g. from `MAZE_ALGORITHMS`.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == name)
+    }
+
+    pub fn generator(self) -> &'static dyn MazeGenerator {
+        match self {
+            Algorithm::Backtracker => &Backtracker,
+            Algorithm::Prim => &Prim,
+            Algorithm::Kruskal => &Kruskal,
+            Algorithm::Wilson => &Wilson,
+            Algorithm::Eller => &Eller,
+            Algorithm::BinaryTree => &BinaryTree,
+            Algorithm::Rooms => &Rooms,
+        }
+    }
+}
+
+/// Carves a maze into a grid of walls. Implementations draw every random choice from `rng`,
+/// in a fixed order, so the result depends on the seed alone.
+pub trait MazeGenerator {
+    /// Row-major `width` x `height` grid (both odd), `0` passage / `1` wall, in which every
+    /// passage is reachable from every other.
+    fn carve(&self, rng: &mut MazeRng, width: usize, height: usize) -> Vec<u8>;
+}
+
+/// Row-major maze of `width` x `height` cells, `0` passage / `1` wall, as sent in
+/// `ServerMessage::Map`. Both sides must be odd. Hard mazes keep what `algorithm` carved
+/// (a perfect maze, except for rooms); easier ones have dead ends opened into loops.
+pub fn generate(seed: u64, width: usize, height: usize, difficulty: Difficulty, algorithm: Algorithm) -> Vec<u8> {
+    generate_with(seed, width, height, dead_end_percent(difficulty), algorithm)
+}
+
+/// Like `generate`, with the share of dead ends opened into loops given directly, in percent.
+pub fn generate_with(seed: u64, width: usize, height: usize, dead_end_percent: usize, algorithm: Algorithm) -> Vec<u8> {
+    assert!(width % 2 == 1 && height % 2 == 1 && width >= 3 && height >= 3);
+    let mut rng = MazeRng::new(seed);
+    let mut cells = algorithm.generator().carve(&mut rng, width, height);
+    if dead_end_percent > 0 {
+        reduce_dead_ends(&mut rng, &mut cells, width, height, dead_end_percent.min(100));
+    }
+    cells
+}
+
+/// Percent of dead ends `generate` opens at each difficulty; hard keeps what was carved.
+pub fn dead_end_percent(difficulty: Difficulty) -> usize {
+    match difficulty {
+        Difficulty::Easy => 50,
+        Difficulty::Medium => 20,
+        Difficulty::Hard => 0,
+    }
+}
+
+/// The passage cells of a maze grid: `(cx, cy)` sits at grid `(2cx + 1, 2cy + 1)`.
+struct Cells {
+    grid: Vec<u8>,
+    w: usize,
+    cw: usize,
+    ch: usize,
+}
+
+impl Cells {
+    fn new(w: usize, h: usize) -> Self {
+        Self { grid: vec![1u8; w * h], w, cw: (w - 1) / 2, ch: (h - 1) / 2 }
+    }
+
+    fn index(&self, (cx, cy): (usize, usize)) -> usize {
+        cy * self.cw + cx
+    }
+
+    fn open(&mut self, (cx, cy): (usize, usize)) {
+        self.grid[(2 * cy + 1) * self.w + (2 * cx + 1)] = 0;
+    }
+
+    /// Opens both cells and the wall between them; they must be neighbours.
+    fn link(&mut self, a: (usize, usize), b: (usize, usize)) {
+        self.open(a);
+        self.open(b);
+        self.grid[(a.1 + b.1 + 1) * self.w + (a.0 + b.0 + 1)] = 0;
+    }
+
+    /// Neighbouring cells in a fixed order (west, north, east, south).
+    fn neighbours(&self, (cx, cy): (usize, usize)) -> Vec<(usize, usize)> {
+        let mut n = Vec::with_capacity(4);
+        if cx > 0 { n.push((cx - 1, cy)); }
+        if cy > 0 { n.push((cx, cy - 1)); }
+        if cx + 1 < self.cw { n.push((cx + 1, cy)); }
+        if cy + 1 < self.ch { n.push((cx, cy + 1)); }
+        n
+    }
+
+    fn all(&self) -> impl Iterator<Item = (usize, usize)> {
+        let cw = self.cw;
+        (0..self.cw * self.ch).map(move |i| (i % cw, i / cw))
+    }
+}
+
+/// Recursive backtracker.
+pub struct Backtracker;
+
+impl MazeGenerator for Backtracker {
+    fn carve(&self, rng: &mut MazeRng, w: usize, h: usize) -> Vec<u8> {
+        // Perfect maze via recursive backtracker on cell grid (odd-sized grid)
+        let cw = (w - 1) / 2; // cells in x
+        let ch = (h - 1) / 2; // cells in y
+        let mut grid = vec![1u8; w * h]; // 1=wall, 0=passage
+        let mut visited = vec![false; cw * ch];
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+
+        push_cell(&mut grid, &mut visited, w, cw, 0, 0, &mut stack);
+        while let Some((cx, cy)) = stack.pop() {
+            // collect neighbors
+            let mut neighbors = Vec::new();
+            if cx > 0 && !visited[cy * cw + (cx - 1)] { neighbors.push((cx - 1, cy, (2*cy+1, 2*cx))); }
+            if cy > 0 && !visited[(cy - 1) * cw + cx] { neighbors.push((cx, cy - 1, (2*cy, 2*cx+1))); }
+            if cx + 1 < cw && !visited[cy * cw + (cx + 1)] { neighbors.push((cx + 1, cy, (2*cy+1, 2*cx+2))); }
+            if cy + 1 < ch && !visited[(cy + 1) * cw + cx] { neighbors.push((cx, cy + 1, (2*cy+2, 2*cx+1))); }
+
+            if !neighbors.is_empty() {
+                // push current back to continue later
+                stack.push((cx, cy));
+                let (nx, ny, (wy, wx)) = neighbors[rng.below(neighbors.len())];
+                // knock down wall between (cx,cy) and (nx,ny)
+                grid[wy * w + wx] = 0;
+                push_cell(&mut grid, &mut visited, w, cw, nx, ny, &mut stack);
+            }
+        }
+
+        grid
+    }
+}
+
+fn push_cell(
+    grid: &mut [u8],
+    visited: &mut [bool],
+    w: usize,
+    cw: usize,
+    cx: usize,
+    cy: usize,
+    stack: &mut Vec<(usize, usize)>,
+) {
+    visited[cy * cw + cx] = true;
+    grid[(2 * cy + 1) * w + (2 * cx + 1)] = 0;
+    stack.push((cx, cy));
+}
+
+/// Randomized Prim: grows the maze from one cell through a random frontier edge at a time.
+pub struct Prim;
+
+impl MazeGenerator for Prim {
+    fn carve(&self, rng: &mut MazeRng, w: usize, h: usize) -> Vec<u8> {
+        let mut cells = Cells::new(w, h);
+        let mut inside = vec![false; cells.cw * cells.ch];
+        let start = (rng.below(cells.cw), rng.below(cells.ch));
+        let mut frontier: Vec<((usize, usize), (usize, usize))> = Vec::new();
+        let i = cells.index(start);
+        inside[i] = true;
+        cells.open(start);
+        frontier.extend(cells.neighbours(start).into_iter().map(|n| (start, n)));
+        while !frontier.is_empty() {
+            let (from, to) = frontier.swap_remove(rng.below(frontier.len()));
+            let i = cells.index(to);
+            if inside[i] { continue; }
+            inside[i] = true;
+            cells.link(from, to);
+            frontier.extend(cells.neighbours(to).into_iter().filter(|n| !inside[cells.index(*n)]).map(|n| (to, n)));
+        }
+        cells.grid
+    }
+}
+
+/// Randomized Kruskal: links cells along shuffled edges unless already connected.
+pub struct Kruskal;
+
+impl MazeGenerator for Kruskal {
+    fn carve(&self, rng: &mut MazeRng, w: usize, h: usize) -> Vec<u8> {
+        let mut cells = Cells::new(w, h);
+        let mut edges: Vec<((usize, usize), (usize, usize))> = Vec::new();
+        for (cx, cy) in cells.all() {
+            cells.open((cx, cy));
+            if cx + 1 < cells.cw { edges.push(((cx, cy), (cx + 1, cy))); }
+            if cy + 1 < cells.ch { edges.push(((cx, cy), (cx, cy + 1))); }
+        }
+        shuffle(rng, &mut edges);
+        let mut parent: Vec<usize> = (0..cells.cw * cells.ch).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for (a, b) in edges {
+            let (ra, rb) = (root(&mut parent, cells.index(a)), root(&mut parent, cells.index(b)));
+            if ra != rb {
+                parent[ra] = rb;
+                cells.link(a, b);
+            }
+        }
+        cells.grid
+    }
+}
+
+/// Wilson's algorithm: loop-erased random walks until every cell joins the tree, which
+/// samples uniformly among all spanning trees.
+pub struct Wilson;
+
+impl MazeGenerator for Wilson {
+    fn carve(&self, rng: &mut MazeRng, w: usize, h: usize) -> Vec<u8> {
+        let mut cells = Cells::new(w, h);
+        let count = cells.cw * cells.ch;
+        let mut in_tree = vec![false; count];
+        // Where the walk last left each cell; following it from the start erases loops
+        let mut next: Vec<(usize, usize)> = vec![(0, 0); count];
+        let first = (rng.below(cells.cw), rng.below(cells.ch));
+        in_tree[cells.index(first)] = true;
+        cells.open(first);
+        let order: Vec<(usize, usize)> = cells.all().collect();
+        for start in order {
+            if in_tree[cells.index(start)] { continue; }
+            let mut at = start;
+            while !in_tree[cells.index(at)] {
+                let n = cells.neighbours(at);
+                let step = n[rng.below(n.len())];
+                let i = cells.index(at);
+                next[i] = step;
+                at = step;
+            }
+            let mut at = start;
+            while !in_tree[cells.index(at)] {
+                let i = cells.index(at);
+                in_tree[i] = true;
+                cells.link(at, next[i]);
+                at = next[i];
+            }
+        }
+        cells.grid
+    }
+}
+
+/// Eller's algorithm: one row at a time, keeping track of which cells are already connected.
+pub struct Eller;
+
+impl MazeGenerator for Eller {
+    fn carve(&self, rng: &mut MazeRng, w: usize, h: usize) -> Vec<u8> {
+        let mut cells = Cells::new(w, h);
+        let (cw, ch) = (cells.cw, cells.ch);
+        let mut sets: Vec<usize> = (0..cw).collect();
+        let mut next_set = cw;
+        for cy in 0..ch {
+            let last = cy + 1 == ch;
+            for cx in 0..cw { cells.open((cx, cy)); }
+            // Join neighbours in different sets: at random, or all of them on the last row
+            for cx in 0..cw.saturating_sub(1) {
+                if sets[cx] != sets[cx + 1] && (last || rng.below(2) == 0) {
+                    let (keep, gone) = (sets[cx], sets[cx + 1]);
+                    for s in sets.iter_mut().filter(|s| **s == gone) { *s = keep; }
+                    cells.link((cx, cy), (cx + 1, cy));
+                }
+            }
+            if last { break; }
+            // Every set continues down at least once
+            let mut below: Vec<Option<usize>> = vec![None; cw];
+            let mut groups: Vec<usize> = sets.clone();
+            groups.sort_unstable();
+            groups.dedup();
+            for set in groups {
+                let members: Vec<usize> = (0..cw).filter(|cx| sets[*cx] == set).collect();
+                let forced = members[rng.below(members.len())];
+                for cx in members {
+                    if cx == forced || rng.below(3) == 0 {
+                        below[cx] = Some(set);
+                        cells.link((cx, cy), (cx, cy + 1));
+                    }
+                }
+            }
+            sets = below
+                .into_iter()
+                .map(|s| s.unwrap_or_else(|| { next_set += 1; next_set }))
+                .collect();
+        }
+        cells.grid
+    }
+}
+
+/// Binary tree: each cell links to its north or west neighbour.
+pub struct BinaryTree;
+
+impl MazeGenerator for BinaryTree {
+    fn carve(&self, rng: &mut MazeRng, w: usize, h: usize) -> Vec<u8> {
+        let mut cells = Cells::new(w, h);
+        let order: Vec<(usize, usize)> = cells.all().collect();
+        for (cx, cy) in order {
+            cells.open((cx, cy));
+            let choices: Vec<(usize, usize)> = [(cx > 0).then(|| (cx - 1, cy)), (cy > 0).then(|| (cx, cy - 1))]
+                .into_iter()
+                .flatten()
+                .collect();
+            if !choices.is_empty() {
+                cells.link((cx, cy), choices[rng.below(choices.len())]);
+            }
+        }
+        cells.grid
+    }
+}
+
+/// Rooms and corridors: a backtracker maze with open rooms of 2 to 4 cells a side dug into
+/// it, roughly one per 40 cells. Rooms only remove walls, so everything stays connected.
+pub struct Rooms;
+
+impl MazeGenerator for Rooms {
+    fn carve(&self, rng: &mut MazeRng, w: usize, h: usize) -> Vec<u8> {
+        let mut grid = Backtracker.carve(rng, w, h);
+        let (cw, ch) = ((w - 1) / 2, (h - 1) / 2);
+        for _ in 0..(cw * ch / 40).max(1) {
+            let (rw, rh) = ((2 + rng.below(3)).min(cw), (2 + rng.below(3)).min(ch));
+            let (x0, y0) = (rng.below(cw - rw + 1), rng.below(ch - rh + 1));
+            for y in 2 * y0 + 1..=2 * (y0 + rh - 1) + 1 {
+                for x in 2 * x0 + 1..=2 * (x0 + rw - 1) + 1 {
+                    grid[y * w + x] = 0;
+                }
+            }
+        }
+        grid
+    }
+}
+
+/// Fisher-Yates with `rng`.
+fn shuffle<T>(rng: &mut MazeRng, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i + 1));
+    }
+}
+
+/// Opens `percent` of the dead ends into loops.
+fn reduce_dead_ends(rng: &mut MazeRng, cells: &mut [u8], w: usize, h: usize, percent: usize) {
+    let mut dead_ends: Vec<(usize, usize)> = Vec::new();
+    for y in 1..(h - 1) {
+        for x in 1..(w - 1) {
+            if cells[y * w + x] == 0 {
+                let mut walls = 0;
+                if cells[(y - 1) * w + x] == 1 { walls += 1; }
+                if cells[(y + 1) * w + x] == 1 { walls += 1; }
+                if cells[y * w + (x - 1)] == 1 { walls += 1; }
+                if cells[y * w + (x + 1)] == 1 { walls += 1; }
+                if walls == 3 { dead_ends.push((x, y)); }
+            }
+        }
+    }
+    let target = dead_ends.len() * percent / 100;
+    for _ in 0..target {
+        if dead_ends.is_empty() { break; }
+        let (x, y) = dead_ends.remove(rng.below(dead_ends.len()));
+        // carve a random adjacent wall to open a loop; the outer border stays closed
+        let mut candidates = Vec::new();
+        if y > 1 && cells[(y - 1) * w + x] == 1 { candidates.push((x, y - 1)); }
+        if y + 2 < h && cells[(y + 1) * w + x] == 1 { candidates.push((x, y + 1)); }
+        if x > 1 && cells[y * w + (x - 1)] == 1 { candidates.push((x - 1, y)); }
+        if x + 2 < w && cells[y * w + (x + 1)] == 1 { candidates.push((x + 1, y)); }
+        if !candidates.is_empty() {
+            let (wx, wy) = candidates[rng.below(candidates.len())];
+            cells[wy * w + wx] = 0;
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Movement rules shared by the server and client-side prediction: one cell per input in
+//! `Grid` mode, or velocity integrated every tick in `Continuous` mode.
+//! Both sides must resolve an input identically, otherwise the client's predicted position
+//! drifts from the server's and every snapshot snaps it back.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rate::TokenBucket;
+
+/// Average time between two actions of one player. The server refuses faster input, and
+/// clients pace themselves with the same [`StepLimiter`].
+pub const STEP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many actions a player may bank. Network jitter can bunch evenly paced inputs together,
+/// and refusing those would make honest clients mispredict.
+const STEP_BURST: u32 = 3;
+
+/// Compass direction a player looks in; `North` is towards row 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Facing {
+    #[default]
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Facing {
+    pub const ALL: [Facing; 4] = [Facing::North, Facing::East, Facing::South, Facing::West];
+
+    /// Grid offset of one step forward.
+    pub fn delta(self) -> (i32, i32) {
+        match self {
+            Facing::North => (0, -1),
+            Facing::East => (1, 0),
+            Facing::South => (0, 1),
+            Facing::West => (-1, 0),
+        }
+    }
+
+    pub fn left(self) -> Self {
+        Self::ALL[(self as usize + 3) % 4]
+    }
+
+    pub fn right(self) -> Self {
+        Self::ALL[(self as usize + 1) % 4]
+    }
+
+    pub fn opposite(self) -> Self {
+        Self::ALL[(self as usize + 2) % 4]
+    }
+}
+
+/// One player command, relative to the current facing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Forward,
+    Back,
+    TurnLeft,
+    TurnRight,
+    AboutFace,
+    StrafeLeft,
+    StrafeRight,
+}
+
+/// Player position and facing; the part of the state actions change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pose {
+    pub x: usize,
+    pub y: usize,
+    pub facing: Facing,
+}
+
+/// Resolves `action` for a player at `pose` in the row-major maze from `ServerMessage::Map`.
+/// Moves never change facing; a blocked move leaves the player where it was.
+pub fn apply(cells: &[u8], width: usize, height: usize, pose: Pose, action: Action) -> Pose {
+    let facing = pose.facing;
+    let heading = match action {
+        Action::TurnLeft => return Pose { facing: facing.left(), ..pose },
+        Action::TurnRight => return Pose { facing: facing.right(), ..pose },
+        Action::AboutFace => return Pose { facing: facing.opposite(), ..pose },
+        Action::Forward => facing,
+        Action::Back => facing.opposite(),
+        Action::StrafeLeft => facing.left(),
+        Action::StrafeRight => facing.right(),
+    };
+    let (dx, dy) = heading.delta();
+    let (x, y) = step(cells, width, height, (pose.x, pose.y), dx, dy);
+    Pose { x, y, facing }
+}
+
+/// Where a `(dx, dy)` move takes a player standing at `(x, y)`. Every traversed cell must be
+/// open and in bounds, otherwise the player stays in place; diagonal moves are never allowed.
+pub fn step(cells: &[u8], width: usize, height: usize, (x, y): (usize, usize), dx: i32, dy: i32) -> (usize, usize) {
+    if dx != 0 && dy != 0 {
+        return (x, y);
+    }
+    let (mut cx, mut cy) = (x as i64, y as i64);
+    for _ in 0..dx.abs().max(dy.abs()) {
+        cx += dx.signum() as i64;
+        cy += dy.signum() as i64;
+        let open = cx >= 0 && cy >= 0 && (cx as usize) < width && (cy as usize) < height
+            && cells.get(cy as usize * width + cx as usize) == Some(&0);
+        if !open {
+            return (x, y);
+        }
+    }
+    (cx as usize, cy as usize)
+}
+
+/// A facing that looks down an open corridor from `(x, y)`, so nobody spawns facing a wall.
+pub fn open_facing(cells: &[u8], width: usize, height: usize, (x, y): (usize, usize)) -> Facing {
+    Facing::ALL
+        .into_iter()
+        .find(|f| {
+            let (dx, dy) = f.delta();
+            step(cells, width, height, (x, y), dx, dy) != (x, y)
+        })
+        .unwrap_or_default()
+}
+
+/// Allows one action per [`STEP_INTERVAL`] on average, with a small burst.
+pub type StepLimiter = TokenBucket<STEP_BURST, { STEP_INTERVAL.as_millis() as u64 }>;
+
+/// How players move; chosen by the server and announced in `Welcome`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MovementMode {
+    /// Classic Maze War: one cell per `Input`, four facings.
+    #[default]
+    Grid,
+    /// Free movement for a first-person view: clients send `Steer`, the server integrates
+    /// `f32` positions and yaw on every tick.
+    Continuous,
+}
+
+impl MovementMode {
+    /// Parses `grid` or `continuous`, e.g. from `MAZE_MOVEMENT`.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "grid" => Some(MovementMode::Grid),
+            "continuous" => Some(MovementMode::Continuous),
+            _ => None,
+        }
+    }
+}
+
+/// Top speed in continuous mode, in cells per second.
+pub const RUN_SPEED: f32 = 4.0;
+/// Turn rate in continuous mode, in radians per second.
+pub const TURN_SPEED: f32 = std::f32::consts::PI;
+/// Radius of the circle players collide with walls as, in cells.
+pub const PLAYER_RADIUS: f32 = 0.3;
+
+/// Continuous position and view direction. Cell `(x, y)` spans `x..x + 1` and `y..y + 1`;
+/// `yaw` is in radians, `0` looking east and growing towards south (rows grow downwards).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Motion {
+    pub x: f32,
+    pub y: f32,
+    pub yaw: f32,
+}
+
+impl Motion {
+    /// Standing in the middle of `(x, y)`, looking towards `facing`.
+    pub fn centered((x, y): (usize, usize), facing: Facing) -> Self {
+        let yaw = match facing {
+            Facing::East => 0.0,
+            Facing::South => std::f32::consts::FRAC_PI_2,
+            Facing::West => std::f32::consts::PI,
+            Facing::North => 3.0 * std::f32::consts::FRAC_PI_2,
+        };
+        Self { x: x as f32 + 0.5, y: y as f32 + 0.5, yaw }
+    }
+
+    /// Grid cell the player stands in.
+    pub fn cell(&self) -> (usize, usize) {
+        (self.x.max(0.0) as usize, self.y.max(0.0) as usize)
+    }
+
+    /// Compass direction closest to `yaw`, for the grid-based rules (shooting, visibility).
+    pub fn facing(&self) -> Facing {
+        let quarter = (self.yaw / std::f32::consts::FRAC_PI_2).round().rem_euclid(4.0) as usize;
+        [Facing::East, Facing::South, Facing::West, Facing::North][quarter]
+    }
+}
+
+/// Held movement keys in continuous mode, each from `-1` to `1`. `forward` and `strafe` move
+/// along and across the view direction (strafe positive to the right), `turn` positive
+/// turns right.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Steering {
+    pub forward: f32,
+    pub strafe: f32,
+    pub turn: f32,
+}
+
+impl Steering {
+    /// Clamps every axis to `-1..=1`; anything not a number counts as released.
+    pub fn clamped(self) -> Self {
+        let axis = |v: f32| if v.is_nan() { 0.0 } else { v.clamp(-1.0, 1.0) };
+        Self { forward: axis(self.forward), strafe: axis(self.strafe), turn: axis(self.turn) }
+    }
+}
+
+/// Advances `motion` by `dt` seconds of `steering` in the row-major maze from
+/// `ServerMessage::Map`. The player is a circle of `PLAYER_RADIUS`; each axis is moved
+/// separately and undone if it would overlap a wall, so players slide along walls.
+pub fn integrate(cells: &[u8], width: usize, height: usize, motion: Motion, steering: Steering, dt: f32) -> Motion {
+    let steering = steering.clamped();
+    let yaw = (motion.yaw + steering.turn * TURN_SPEED * dt).rem_euclid(std::f32::consts::TAU);
+    let (sin, cos) = yaw.sin_cos();
+    let (mut vx, mut vy) = (cos * steering.forward - sin * steering.strafe, sin * steering.forward + cos * steering.strafe);
+    // Diagonal input is no faster than straight ahead
+    let len = (vx * vx + vy * vy).sqrt();
+    if len > 1.0 { vx /= len; vy /= len; }
+    let (mut x, mut y) = (motion.x, motion.y);
+    let nx = x + vx * RUN_SPEED * dt;
+    if !hits_wall(cells, width, height, nx, y) { x = nx; }
+    let ny = y + vy * RUN_SPEED * dt;
+    if !hits_wall(cells, width, height, x, ny) { y = ny; }
+    Motion { x, y, yaw }
+}
+
+/// Whether a player circle centred at `(x, y)` overlaps a wall or leaves the maze.
+fn hits_wall(cells: &[u8], width: usize, height: usize, x: f32, y: f32) -> bool {
+    let r = PLAYER_RADIUS;
+    if x - r < 0.0 || y - r < 0.0 || x + r > width as f32 || y + r > height as f32 {
+        return true;
+    }
+    for cy in (y - r) as usize..=((y + r) as usize).min(height - 1) {
+        for cx in (x - r) as usize..=((x + r) as usize).min(width - 1) {
+            if cells.get(cy * width + cx) == Some(&0) { continue; }
+            // Closest point of the wall cell to the centre
+            let px = x.clamp(cx as f32, cx as f32 + 1.0);
+            let py = y.clamp(cy as f32, cy as f32 + 1.0);
+            if (px - x) * (px - x) + (py - y) * (py - y) < r * r {
+                return true;
+            }
+        }
+    }
+    false
+}
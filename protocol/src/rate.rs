@@ -0,0 +1,38 @@
+//! Token bucket rate limiting, for movement input and chat alike.
+
+use std::time::{Duration, Instant};
+
+/// Allows one event per `INTERVAL_MS` milliseconds on average, and up to `BURST` at once
+/// after a quiet spell. Starts full.
+#[derive(Debug, Clone)]
+pub struct TokenBucket<const BURST: u32, const INTERVAL_MS: u64> {
+    allowance: f32,
+    last: Instant,
+}
+
+impl<const BURST: u32, const INTERVAL_MS: u64> Default for TokenBucket<BURST, INTERVAL_MS> {
+    fn default() -> Self {
+        Self { allowance: BURST as f32, last: Instant::now() }
+    }
+}
+
+impl<const BURST: u32, const INTERVAL_MS: u64> TokenBucket<BURST, INTERVAL_MS> {
+    pub const INTERVAL: Duration = Duration::from_millis(INTERVAL_MS);
+
+    /// Spends one token if the budget allows it.
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    /// `try_take` at a given time; earlier times than the last call refill nothing.
+    pub fn try_take_at(&mut self, now: Instant) -> bool {
+        let refill = now.saturating_duration_since(self.last).as_secs_f32() / Self::INTERVAL.as_secs_f32();
+        self.allowance = (self.allowance + refill).min(BURST as f32);
+        self.last = self.last.max(now);
+        if self.allowance < 1.0 {
+            return false;
+        }
+        self.allowance -= 1.0;
+        true
+    }
+}
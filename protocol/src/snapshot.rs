@@ -0,0 +1,85 @@
+//! Tick-stamped player snapshots and their delta encoding.
+//! The server diffs each snapshot against the last tick a client acknowledged; the client
+//! keeps a short history of resolved snapshots so it can rebuild the full table.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::PlayerState;
+
+/// Full player table at one tick, keyed by player id.
+pub type PlayerTable = BTreeMap<u64, PlayerState>;
+
+/// How many resolved snapshots either side keeps around as delta baselines.
+pub const SNAPSHOT_HISTORY: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Snapshot {
+    /// Server tick this snapshot was taken at; strictly increasing.
+    pub tick: u64,
+    /// Tick this delta is relative to, or `None` for a full snapshot.
+    pub baseline: Option<u64>,
+    /// Players that are new or changed since `baseline`.
+    pub changed: Vec<PlayerState>,
+    /// Players present at `baseline` that are gone now.
+    pub removed: Vec<u64>,
+}
+
+impl Snapshot {
+    pub fn full(tick: u64, current: &PlayerTable) -> Self {
+        Self { tick, baseline: None, changed: current.values().copied().collect(), removed: Vec::new() }
+    }
+
+    /// Encodes `current` relative to `(baseline_tick, baseline_table)`.
+    pub fn delta(tick: u64, current: &PlayerTable, baseline_tick: u64, baseline: &PlayerTable) -> Self {
+        let changed = current.values().filter(|p| baseline.get(&p.id) != Some(*p)).copied().collect();
+        let removed = baseline.keys().filter(|id| !current.contains_key(id)).copied().collect();
+        Self { tick, baseline: Some(baseline_tick), changed, removed }
+    }
+
+    /// Rebuilds the full table from the table at `self.baseline` (ignored for full snapshots).
+    pub fn apply(&self, baseline: &PlayerTable) -> PlayerTable {
+        let mut table = if self.baseline.is_some() { baseline.clone() } else { PlayerTable::new() };
+        for id in &self.removed {
+            table.remove(id);
+        }
+        for p in &self.changed {
+            table.insert(p.id, *p);
+        }
+        table
+    }
+}
+
+/// Client-side history of resolved snapshots.
+#[derive(Debug, Default)]
+pub struct SnapshotReceiver {
+    history: VecDeque<(u64, PlayerTable)>,
+}
+
+impl SnapshotReceiver {
+    /// Resolves an incoming snapshot. Returns `None` when it is older than the latest one
+    /// (unreliable delivery reorders) or its baseline is no longer known.
+    pub fn receive(&mut self, snapshot: &Snapshot) -> Option<&PlayerTable> {
+        if self.latest_tick().is_some_and(|latest| snapshot.tick <= latest) {
+            return None;
+        }
+        let table = match snapshot.baseline {
+            None => snapshot.apply(&PlayerTable::new()),
+            Some(base) => {
+                let (_, baseline) = self.history.iter().find(|(tick, _)| *tick == base)?;
+                snapshot.apply(baseline)
+            }
+        };
+        if self.history.len() == SNAPSHOT_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back((snapshot.tick, table));
+        self.history.back().map(|(_, table)| table)
+    }
+
+    pub fn latest_tick(&self) -> Option<u64> {
+        self.history.back().map(|(tick, _)| *tick)
+    }
+}
@@ -0,0 +1,71 @@
+use protocol::analysis::MazeStats;
+use protocol::maze::{generate, Algorithm};
+use protocol::{Difficulty, MapSize};
+
+/// A 7x5 grid: a ring around a central wall, with a spur running right from (3, 2) to (5, 2).
+///
+/// ```text
+/// #######
+/// #   ###
+/// # #   #
+/// #   ###
+/// #######
+/// ```
+fn ring_with_spur() -> (Vec<u8>, usize, usize) {
+    let rows = ["#######", "#   ###", "# #   #", "#   ###", "#######"];
+    let cells = rows.iter().flat_map(|r| r.chars().map(|c| (c == '#') as u8)).collect();
+    (cells, 7, 5)
+}
+
+#[test]
+fn metrics_of_a_small_grid() {
+    let (cells, w, h) = ring_with_spur();
+    let stats = MazeStats::analyse(&cells, w, h, &[(1, 1), (5, 2)]);
+    assert_eq!(stats.open_cells, 10);
+    assert_eq!(stats.dead_ends, 1);
+    assert_eq!(stats.loops, 1);
+    assert_eq!(stats.junctions, 1);
+    assert_eq!(stats.branching_factor, 2.0);
+    // From the spur's end round to the far side of the ring
+    assert_eq!(stats.longest_path, 6);
+    assert_eq!(stats.spawn_distances, vec![5]);
+    // The spur (2 steps) and the ring back to the junction (8 steps)
+    assert_eq!(stats.avg_corridor, 5.0);
+}
+
+#[test]
+fn perfect_mazes_have_no_loops() {
+    let (w, h) = MapSize::Small.dimensions();
+    for algorithm in Algorithm::ALL.into_iter().filter(|a| *a != Algorithm::Rooms) {
+        let stats = MazeStats::analyse(&generate(11, w, h, Difficulty::Hard, algorithm), w, h, &[]);
+        assert_eq!(stats.loops, 0, "{algorithm:?}");
+    }
+}
+
+/// Averages over a few seeds, so one lucky maze cannot flip the order.
+fn average(algorithm: Algorithm, difficulty: Difficulty) -> (f32, f32, f32) {
+    let (w, h) = MapSize::Medium.dimensions();
+    let seeds = 0..8u64;
+    let n = seeds.clone().count() as f32;
+    let (mut dead_ends, mut loops, mut longest) = (0.0, 0.0, 0.0);
+    for seed in seeds {
+        let stats = MazeStats::analyse(&generate(seed, w, h, difficulty, algorithm), w, h, &[]);
+        dead_ends += stats.dead_ends as f32 / n;
+        loops += stats.loops as f32 / n;
+        longest += stats.longest_path as f32 / n;
+    }
+    (dead_ends, loops, longest)
+}
+
+#[test]
+fn easy_is_easier_than_medium_is_easier_than_hard() {
+    for algorithm in Algorithm::ALL {
+        let [easy, medium, hard] = Difficulty::ALL.map(|d| average(algorithm, d));
+        // More dead ends to get lost in
+        assert!(easy.0 < medium.0 && medium.0 < hard.0, "{algorithm:?} dead ends: {easy:?} {medium:?} {hard:?}");
+        // Fewer loops to cut corners with
+        assert!(easy.1 > medium.1 && medium.1 > hard.1, "{algorithm:?} loops: {easy:?} {medium:?} {hard:?}");
+        // Longer ways across
+        assert!(easy.2 <= medium.2 && medium.2 <= hard.2, "{algorithm:?} longest path: {easy:?} {medium:?} {hard:?}");
+    }
+}
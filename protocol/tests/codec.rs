@@ -0,0 +1,90 @@
+use protocol::{Action, ClientMessage, Codec, Facing, PlayerState, ServerFrame, ServerMessage, PROTOCOL_VERSION};
+
+fn hello(resume: Option<u64>) -> ClientMessage {
+    ClientMessage::Hello { version: PROTOCOL_VERSION, build: "test".into(), name: "alice".into(), codec: Codec::Binary, resume }
+}
+
+#[test]
+fn first_hello_leaves_out_resume() {
+    // Servers from before sessions reject unknown fields, so a fresh Hello must not send one
+    let json = String::from_utf8(hello(None).to_bytes(Codec::Json)).unwrap();
+    assert!(!json.contains("resume"), "{json}");
+    assert_eq!(ClientMessage::from_bytes(json.as_bytes()).unwrap(), hello(None));
+
+    let resuming = hello(Some(42)).to_bytes(Codec::Json);
+    assert_eq!(ClientMessage::from_bytes(&resuming).unwrap(), hello(Some(42)));
+}
+
+fn client_messages() -> Vec<ClientMessage> {
+    vec![
+        hello(Some(7)),
+        ClientMessage::Input { seq: 3, action: Action::StrafeLeft },
+        ClientMessage::Ack { tick: u64::MAX },
+        ClientMessage::Shoot,
+        ClientMessage::VoteLevel { level: 2 },
+        ClientMessage::Chat { text: "hé, ünïcode".into(), team_only: true },
+    ]
+}
+
+fn map(cells: Vec<u8>) -> ServerMessage {
+    ServerMessage::Map { width: 5, height: 3, cells, exit: Some((3, 1)), seed: None, dead_ends: 0 }
+}
+
+#[test]
+fn every_codec_round_trips() {
+    for codec in [Codec::Json, Codec::Binary] {
+        for message in client_messages() {
+            let bytes = message.to_bytes(codec);
+            assert_eq!(ClientMessage::from_bytes(&bytes).unwrap(), message, "{codec:?}");
+        }
+        let players = ServerMessage::PlayerInit(PlayerState { id: u64::MAX, x: 3, y: 1, facing: Facing::West, last_input: u32::MAX, team: Some(7), motion: None });
+        assert_eq!(ServerMessage::from_bytes(&players.to_bytes(codec)).unwrap(), players, "{codec:?}");
+        let frame = ServerFrame { tick: 99, message: map(vec![1, 1, 1, 1, 1, 1, 0, 0, 0, 1, 1, 1, 1, 1, 1]) };
+        assert_eq!(ServerFrame::from_bytes(&frame.to_bytes(codec)).unwrap(), frame, "{codec:?}");
+    }
+}
+
+#[test]
+fn frames_are_told_apart_by_their_first_byte() {
+    for message in client_messages() {
+        assert_eq!(message.to_bytes(Codec::Binary)[0], 0xB1, "{message:?}");
+        assert!(matches!(message.to_bytes(Codec::Json)[0], b'{' | b'"'), "{message:?}");
+    }
+    // Unit variants are bare JSON strings
+    assert_eq!(ClientMessage::Shoot.to_bytes(Codec::Json), b"\"Shoot\"");
+    assert!(ClientMessage::from_bytes(&[]).is_err());
+    assert!(ClientMessage::from_bytes(&[0xB1, 0xFF, 0xFF]).is_err());
+    assert!(ClientMessage::from_bytes(b"{\"Shoot\"").is_err());
+}
+
+#[test]
+fn binary_packs_cells_into_bits() {
+    let cells: Vec<u8> = (0..41 * 31).map(|i| u8::from(i % 3 == 0)).collect();
+    let message = ServerMessage::Map { width: 41, height: 31, cells: cells.clone(), exit: None, seed: Some(1), dead_ends: 20 };
+    let binary = message.to_bytes(Codec::Binary);
+    assert!(binary.len() < cells.len() / 8 + 32, "{} bytes for {} cells", binary.len(), cells.len());
+    assert_eq!(ServerMessage::from_bytes(&binary).unwrap(), message);
+
+    // JSON keeps the readable 0/1 array
+    let json = String::from_utf8(message.to_bytes(Codec::Json)).unwrap();
+    assert!(json.contains("\"cells\":[1,0,0,1,"), "{json}");
+}
+
+#[test]
+fn packed_cells_send_any_nonzero_cell_as_a_wall() {
+    // 9 cells also checks a partly filled last byte
+    let sent = ServerMessage::Map { width: 3, height: 3, cells: vec![0, 2, 0, 255, 0, 1, 0, 0, 7], exit: None, seed: None, dead_ends: 0 };
+    let ServerMessage::Map { cells, .. } = ServerMessage::from_bytes(&sent.to_bytes(Codec::Binary)).unwrap() else {
+        panic!("expected a map");
+    };
+    assert_eq!(cells, vec![0, 1, 0, 1, 0, 1, 0, 0, 1]);
+}
+
+#[test]
+fn packed_cells_reject_a_count_that_does_not_match_the_bits() {
+    let mut bytes = map(vec![0; 15]).to_bytes(Codec::Binary);
+    // Marker, variant, width, height, then the cell count: claim 17 cells, which need 3 bytes
+    let count = bytes.iter().position(|&b| b == 15).unwrap();
+    bytes[count] = 17;
+    assert!(ServerMessage::from_bytes(&bytes).is_err());
+}
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+
+use protocol::maze::{generate, Algorithm};
+use protocol::{Difficulty, MapSize};
+
+/// FNV-1a, so the expected values below do not depend on std's hasher.
+fn fingerprint(cells: &[u8]) -> u64 {
+    cells.iter().fold(0xcbf2_9ce4_8422_2325, |h, c| (h ^ *c as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[test]
+fn same_inputs_give_identical_cells() {
+    for size in MapSize::ALL {
+        let (w, h) = size.dimensions();
+        for difficulty in Difficulty::ALL {
+            assert_eq!(generate(42, w, h, difficulty, Algorithm::Backtracker), generate(42, w, h, difficulty, Algorithm::Backtracker));
+        }
+    }
+}
+
+#[test]
+fn different_seeds_give_different_mazes() {
+    let (w, h) = MapSize::Medium.dimensions();
+    assert_ne!(generate(1, w, h, Difficulty::Hard, Algorithm::Backtracker), generate(2, w, h, Difficulty::Hard, Algorithm::Backtracker));
+}
+
+/// Fingerprints of seed 7 on a small maze, one per difficulty from easy to hard.
+const PINNED: [(Algorithm, [u64; 3]); 7] = [
+    (Algorithm::Backtracker, [1563091403401299742, 639570560416021898, 15983457974143161307]),
+    (Algorithm::Prim, [12227737882218246739, 3552527619801733258, 7461846801778107827]),
+    (Algorithm::Kruskal, [15763845418845925991, 16684008676355959455, 12844408208924384227]),
+    (Algorithm::Wilson, [6575720394532865839, 4295779998704430959, 17658829188581820531]),
+    (Algorithm::Eller, [949043425166311762, 10177581665890288143, 3651930601862285587]),
+    (Algorithm::BinaryTree, [8882789750135559419, 7213157869384859818, 7955420529823166383]),
+    (Algorithm::Rooms, [2794124666993298843, 15459634701441527063, 14191411224109383575]),
+];
+
+#[test]
+fn output_is_pinned() {
+    // Clients may regenerate mazes from the seed, so the output must never change silently
+    let (w, h) = MapSize::Small.dimensions();
+    assert_eq!(PINNED.map(|(a, _)| a), Algorithm::ALL, "pin every algorithm");
+    for (algorithm, expected) in PINNED {
+        let got = Difficulty::ALL.map(|d| fingerprint(&generate(7, w, h, d, algorithm)));
+        assert_eq!(got, expected, "{algorithm:?}");
+    }
+}
+
+/// Open cells reachable from the first open cell, and all open cells.
+fn reachable(cells: &[u8], w: usize, h: usize) -> (usize, usize) {
+    let open = cells.iter().filter(|c| **c == 0).count();
+    let Some(start) = cells.iter().position(|c| *c == 0) else { return (0, open) };
+    let mut seen = vec![false; w * h];
+    let mut queue = VecDeque::from([start]);
+    seen[start] = true;
+    let mut count = 0;
+    while let Some(i) = queue.pop_front() {
+        count += 1;
+        let (x, y) = (i % w, i / w);
+        for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+            if nx < w && ny < h && cells[ny * w + nx] == 0 && !seen[ny * w + nx] {
+                seen[ny * w + nx] = true;
+                queue.push_back(ny * w + nx);
+            }
+        }
+    }
+    (count, open)
+}
+
+#[test]
+fn every_algorithm_builds_a_closed_connected_maze() {
+    for algorithm in Algorithm::ALL {
+        for size in MapSize::ALL {
+            let (w, h) = size.dimensions();
+            for seed in 0..5 {
+                let cells = generate(seed, w, h, Difficulty::Hard, algorithm);
+                assert_eq!(cells.len(), w * h);
+                let border = (0..w).flat_map(|x| [(x, 0), (x, h - 1)]).chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]));
+                for (x, y) in border {
+                    assert_eq!(cells[y * w + x], 1, "{algorithm:?} opened the border at ({x}, {y})");
+                }
+                for cy in 0..(h - 1) / 2 {
+                    for cx in 0..(w - 1) / 2 {
+                        assert_eq!(cells[(2 * cy + 1) * w + 2 * cx + 1], 0, "{algorithm:?} left cell ({cx}, {cy}) closed");
+                    }
+                }
+                let (reached, open) = reachable(&cells, w, h);
+                assert_eq!(reached, open, "{algorithm:?} seed {seed} is not connected");
+                assert_eq!(cells, generate(seed, w, h, Difficulty::Hard, algorithm));
+            }
+        }
+    }
+}
+
+#[test]
+fn algorithm_names_round_trip() {
+    for algorithm in Algorithm::ALL {
+        assert_eq!(Algorithm::parse(algorithm.as_str()), Some(algorithm));
+    }
+}
@@ -0,0 +1,92 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use protocol::movement::{integrate, PLAYER_RADIUS, RUN_SPEED};
+use protocol::{Facing, Motion, Steering};
+
+/// A 5x5 room: walls all around an open 3x3 middle.
+const WIDTH: usize = 5;
+const HEIGHT: usize = 5;
+const TICK: f32 = 1.0 / 30.0;
+
+fn room() -> Vec<u8> {
+    (0..WIDTH * HEIGHT).map(|i| u8::from(i % WIDTH == 0 || i / WIDTH == 0 || i % WIDTH == WIDTH - 1 || i / WIDTH == HEIGHT - 1)).collect()
+}
+
+fn run(cells: &[u8], width: usize, height: usize, mut motion: Motion, steering: Steering, ticks: usize) -> Motion {
+    for _ in 0..ticks {
+        motion = integrate(cells, width, height, motion, steering, TICK);
+    }
+    motion
+}
+
+fn forward(amount: f32) -> Steering {
+    Steering { forward: amount, ..Default::default() }
+}
+
+#[test]
+fn slides_along_a_wall() {
+    // Heading north-east into the north wall: y stops at the wall, x keeps its full share
+    let start = Motion { x: 1.5, y: 1.5, yaw: -FRAC_PI_4 };
+    let end = run(&room(), WIDTH, HEIGHT, start, forward(1.0), 10);
+    assert!((end.y - (1.0 + PLAYER_RADIUS)).abs() < 0.05, "{end:?}");
+    assert!(end.x > 2.4, "stuck on the wall at {end:?}");
+}
+
+#[test]
+fn diagonal_input_is_no_faster_than_straight() {
+    let start = Motion { x: 2.5, y: 2.5, yaw: 0.0 };
+    let dt = 0.1;
+    let straight = integrate(&room(), WIDTH, HEIGHT, start, forward(1.0), dt);
+    let diagonal = integrate(&room(), WIDTH, HEIGHT, start, Steering { forward: 1.0, strafe: 1.0, turn: 0.0 }, dt);
+    let distance = |m: Motion| ((m.x - start.x).powi(2) + (m.y - start.y).powi(2)).sqrt();
+    assert!((distance(straight) - RUN_SPEED * dt).abs() < 1e-4, "{straight:?}");
+    assert!(distance(diagonal) <= distance(straight) + 1e-4, "{diagonal:?}");
+    // Out of range input counts as full speed, no more
+    let pushed = integrate(&room(), WIDTH, HEIGHT, start, forward(10.0), dt);
+    assert_eq!(pushed, straight);
+}
+
+#[test]
+fn nan_steering_is_ignored() {
+    let start = Motion { x: 2.5, y: 2.5, yaw: 1.0 };
+    let nan = Steering { forward: f32::NAN, strafe: f32::NAN, turn: f32::NAN };
+    let end = integrate(&room(), WIDTH, HEIGHT, start, nan, TICK);
+    assert_eq!(end, start);
+    let half = Steering { forward: 1.0, strafe: f32::NAN, turn: 0.0 };
+    assert_eq!(integrate(&room(), WIDTH, HEIGHT, start, half, TICK), integrate(&room(), WIDTH, HEIGHT, start, forward(1.0), TICK));
+}
+
+#[test]
+fn never_leaves_the_maze() {
+    // No walls at all: only the maze edge stops the player
+    let open = vec![0u8; 9];
+    for step in 0..16 {
+        let yaw = step as f32 * PI / 8.0;
+        let end = run(&open, 3, 3, Motion { x: 1.5, y: 1.5, yaw }, forward(1.0), 60);
+        let r = PLAYER_RADIUS;
+        assert!(end.x >= r && end.y >= r && end.x <= 3.0 - r && end.y <= 3.0 - r, "yaw {yaw}: {end:?}");
+        assert!(end.cell().0 < 3 && end.cell().1 < 3, "yaw {yaw}: {end:?}");
+    }
+}
+
+#[test]
+fn facing_rounds_yaw_to_the_nearest_compass_direction() {
+    for facing in Facing::ALL {
+        assert_eq!(Motion::centered((1, 1), facing).facing(), facing);
+    }
+    let facing = |yaw: f32| Motion { x: 0.5, y: 0.5, yaw }.facing();
+    assert_eq!(facing(0.3), Facing::East);
+    assert_eq!(facing(FRAC_PI_2 + 0.7), Facing::South);
+    assert_eq!(facing(-0.3), Facing::East);
+    assert_eq!(facing(-FRAC_PI_2), Facing::North);
+    assert_eq!(facing(2.0 * PI - 0.1), Facing::East);
+    assert_eq!(facing(5.0 * PI), Facing::West);
+}
+
+#[test]
+fn turning_keeps_yaw_in_one_turn() {
+    let start = Motion { x: 2.5, y: 2.5, yaw: 0.1 };
+    let left = run(&room(), WIDTH, HEIGHT, start, Steering { turn: -1.0, ..Default::default() }, 15);
+    assert!((0.0..2.0 * PI).contains(&left.yaw), "{left:?}");
+    assert_eq!(left.facing(), Facing::North);
+}
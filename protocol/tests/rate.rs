@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+use protocol::movement::{StepLimiter, STEP_INTERVAL};
+use protocol::rate::TokenBucket;
+
+type Bucket = TokenBucket<3, 100>;
+
+#[test]
+fn a_full_bucket_allows_a_burst_then_refuses() {
+    let mut bucket = Bucket::default();
+    let now = Instant::now();
+    assert!((0..3).all(|_| bucket.try_take_at(now)));
+    assert!(!bucket.try_take_at(now));
+}
+
+#[test]
+fn tokens_come_back_one_per_interval() {
+    let mut bucket = Bucket::default();
+    let start = Instant::now();
+    while bucket.try_take_at(start) {}
+    assert!(!bucket.try_take_at(start + Duration::from_millis(50)));
+    assert!(bucket.try_take_at(start + Duration::from_millis(100)));
+    assert!(!bucket.try_take_at(start + Duration::from_millis(100)));
+    // Evenly paced events are never refused
+    for i in 2..50 {
+        assert!(bucket.try_take_at(start + Duration::from_millis(100 * i)), "event {i}");
+    }
+}
+
+#[test]
+fn a_long_pause_banks_no_more_than_the_burst() {
+    let mut bucket = Bucket::default();
+    let later = Instant::now() + Duration::from_secs(60);
+    assert_eq!((0..10).filter(|_| bucket.try_take_at(later)).count(), 3);
+}
+
+#[test]
+fn a_clock_going_backwards_refills_nothing() {
+    let mut bucket = Bucket::default();
+    let now = Instant::now() + Duration::from_secs(1);
+    while bucket.try_take_at(now) {}
+    assert!(!bucket.try_take_at(now - Duration::from_millis(500)));
+    // Nor does it count the same time twice
+    assert!(!bucket.try_take_at(now + Duration::from_millis(50)));
+    assert!(bucket.try_take_at(now + Duration::from_millis(100)));
+}
+
+#[test]
+fn step_limiter_paces_at_the_step_interval() {
+    let mut limiter = StepLimiter::default();
+    let start = Instant::now();
+    // Steps one interval apart are never refused
+    for i in 1..=50 {
+        assert!(limiter.try_take_at(start + STEP_INTERVAL * i), "step {i}");
+    }
+    // A little faster and the burst runs out after a while
+    let paced = start + STEP_INTERVAL * 50;
+    let faster = STEP_INTERVAL * 9 / 10;
+    let allowed = (1..=50).take_while(|i| limiter.try_take_at(paced + faster * *i)).count();
+    assert!((1..50).contains(&allowed), "{allowed} fast steps allowed");
+}
@@ -0,0 +1,87 @@
+use protocol::snapshot::{PlayerTable, SnapshotReceiver, SNAPSHOT_HISTORY};
+use protocol::{Facing, PlayerState, Snapshot};
+
+fn player(id: u64, x: usize, y: usize) -> PlayerState {
+    PlayerState { id, x, y, facing: Facing::North, last_input: 0, team: None, motion: None }
+}
+
+fn table(players: &[PlayerState]) -> PlayerTable {
+    players.iter().map(|p| (p.id, *p)).collect()
+}
+
+#[test]
+fn delta_applies_back_to_the_full_state() {
+    let baseline = table(&[player(1, 1, 1), player(2, 3, 1), player(3, 5, 5)]);
+    let mut moved = player(2, 3, 2);
+    moved.facing = Facing::South;
+    let current = table(&[player(1, 1, 1), moved, player(3, 5, 5), player(4, 7, 7)]);
+
+    let delta = Snapshot::delta(11, &current, 10, &baseline);
+    assert_eq!(delta.baseline, Some(10));
+    // Only the moved and the new player go over the wire
+    assert_eq!(delta.changed, vec![moved, player(4, 7, 7)]);
+    assert!(delta.removed.is_empty());
+    assert_eq!(delta.apply(&baseline), current);
+}
+
+#[test]
+fn removed_players_are_dropped() {
+    let baseline = table(&[player(1, 1, 1), player(2, 3, 1)]);
+    let current = table(&[player(2, 3, 1)]);
+
+    let delta = Snapshot::delta(11, &current, 10, &baseline);
+    assert_eq!(delta.removed, vec![1]);
+    assert!(delta.changed.is_empty());
+    assert_eq!(delta.apply(&baseline), current);
+}
+
+#[test]
+fn full_snapshot_ignores_the_baseline() {
+    let current = table(&[player(1, 1, 1)]);
+    let stale = table(&[player(2, 3, 1)]);
+    assert_eq!(Snapshot::full(5, &current).apply(&stale), current);
+}
+
+#[test]
+fn receiver_resolves_deltas_against_acked_ticks() {
+    let mut receiver = SnapshotReceiver::default();
+    let first = table(&[player(1, 1, 1), player(2, 3, 1)]);
+    assert_eq!(receiver.receive(&Snapshot::full(10, &first)), Some(&first));
+
+    // The server may still diff against an older acked tick after newer ones arrived
+    let second = table(&[player(1, 2, 1), player(2, 3, 1)]);
+    assert_eq!(receiver.receive(&Snapshot::delta(11, &second, 10, &first)), Some(&second));
+    let third = table(&[player(1, 2, 1)]);
+    assert_eq!(receiver.receive(&Snapshot::delta(12, &third, 10, &first)), Some(&third));
+    assert_eq!(receiver.latest_tick(), Some(12));
+}
+
+#[test]
+fn receiver_skips_stale_and_unknown_baselines() {
+    let mut receiver = SnapshotReceiver::default();
+    let first = table(&[player(1, 1, 1)]);
+    let second = table(&[player(1, 2, 1)]);
+    receiver.receive(&Snapshot::full(10, &first));
+    receiver.receive(&Snapshot::full(11, &second));
+
+    // Reordered delivery: older than what the client already has
+    assert_eq!(receiver.receive(&Snapshot::full(9, &first)), None);
+    assert_eq!(receiver.receive(&Snapshot::delta(11, &first, 10, &first)), None);
+    // A baseline the client never saw
+    assert_eq!(receiver.receive(&Snapshot::delta(12, &first, 7, &second)), None);
+    assert_eq!(receiver.latest_tick(), Some(11));
+    // A full snapshot recovers from there
+    assert_eq!(receiver.receive(&Snapshot::full(13, &first)), Some(&first));
+}
+
+#[test]
+fn receiver_forgets_baselines_past_its_history() {
+    let mut receiver = SnapshotReceiver::default();
+    let players = table(&[player(1, 1, 1)]);
+    for tick in 0..=SNAPSHOT_HISTORY as u64 {
+        receiver.receive(&Snapshot::full(tick, &players));
+    }
+    let next = SNAPSHOT_HISTORY as u64 + 1;
+    assert_eq!(receiver.receive(&Snapshot::delta(next, &players, 0, &players)), None);
+    assert_eq!(receiver.receive(&Snapshot::delta(next, &players, 1, &players)), Some(&players));
+}
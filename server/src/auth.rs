@@ -0,0 +1,132 @@
+//! Secure netcode: private key handling and the connect-token issuer.
+//! In secure mode netcode only admits clients holding a token signed with the server's key,
+//! and the username inside that token is the one the server trusts.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use renet_netcode::{generate_random_bytes, ConnectToken, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES};
+use protocol::PROTOCOL_VERSION;
+
+pub type PrivateKey = [u8; NETCODE_KEY_BYTES];
+
+/// How long an issued token can be used to start a connection.
+const TOKEN_EXPIRE_SECS: u64 = 300;
+/// Connection timeout baked into issued tokens.
+const TOKEN_TIMEOUT_SECS: i32 = 15;
+/// Longest username a token may carry.
+pub const MAX_USERNAME_LEN: usize = 32;
+
+pub fn generate_key() -> PrivateKey {
+    generate_random_bytes()
+}
+
+/// Reads a key file: 64 hex characters, surrounding whitespace ignored.
+pub fn read_key_file(path: &Path) -> Result<PrivateKey, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read key file {}: {e}", path.display()))?;
+    let hex = text.trim();
+    if hex.len() != NETCODE_KEY_BYTES * 2 || !hex.is_ascii() {
+        return Err(format!("key file {} must hold {} hex characters", path.display(), NETCODE_KEY_BYTES * 2));
+    }
+    let mut key = [0u8; NETCODE_KEY_BYTES];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("key file {} is not valid hex", path.display()))?;
+    }
+    Ok(key)
+}
+
+pub fn write_key_file(path: &Path, key: &PrivateKey) -> std::io::Result<()> {
+    let hex: String = key.iter().map(|b| format!("{b:02x}")).collect();
+    std::fs::write(path, hex + "\n")
+}
+
+/// Signs a connect token for `username` that is valid for any of `server_addresses`.
+/// The client id is random; netcode refuses a second connection with the same id.
+pub fn issue_token(key: &PrivateKey, server_addresses: Vec<SocketAddr>, username: &str) -> Result<ConnectToken, String> {
+    validate_username(username)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("time");
+    let client_id = rand::random::<u64>();
+    let user_data = username_user_data(username);
+    ConnectToken::generate(
+        now,
+        PROTOCOL_VERSION.protocol_id(),
+        TOKEN_EXPIRE_SECS,
+        client_id,
+        TOKEN_TIMEOUT_SECS,
+        server_addresses,
+        Some(&user_data),
+        key,
+    )
+    .map_err(|e| format!("cannot generate token: {e}"))
+}
+
+fn validate_username(username: &str) -> Result<(), String> {
+    if username.is_empty() || username.len() > MAX_USERNAME_LEN {
+        return Err(format!("username must be 1-{MAX_USERNAME_LEN} bytes"));
+    }
+    if username.chars().any(char::is_control) {
+        return Err("username must not contain control characters".into());
+    }
+    Ok(())
+}
+
+/// Token user data layout: one length byte followed by the UTF-8 username, zero padded.
+fn username_user_data(username: &str) -> [u8; NETCODE_USER_DATA_BYTES] {
+    let mut data = [0u8; NETCODE_USER_DATA_BYTES];
+    data[0] = username.len() as u8;
+    data[1..1 + username.len()].copy_from_slice(username.as_bytes());
+    data
+}
+
+/// Username carried in a token's user data, if it holds one.
+pub fn username_from_user_data(data: &[u8; NETCODE_USER_DATA_BYTES]) -> Option<String> {
+    let len = data[0] as usize;
+    if len == 0 || len > MAX_USERNAME_LEN {
+        return None;
+    }
+    String::from_utf8(data[1..1 + len].to_vec()).ok()
+}
+
+/// Serves connect tokens over TCP, one thread per connection. A client sends its username on one
+/// line and gets the raw token bytes back, or a line starting with `ERR` on failure.
+/// Anyone who can reach this socket can join under any name, so keep it on a trusted address.
+pub fn spawn_token_issuer(addr: &str, key: PrivateKey, server_addresses: Vec<SocketAddr>) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // Each client gets its own thread, so a slow one cannot hold up the rest
+            let server_addresses = server_addresses.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = serve_token(stream, &key, &server_addresses) {
+                    eprintln!("token issuer: {e}");
+                }
+            });
+        }
+    });
+    Ok(local)
+}
+
+fn serve_token(mut stream: TcpStream, key: &PrivateKey, server_addresses: &[SocketAddr]) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut line = String::new();
+    // Bounded so a client cannot make us buffer forever
+    BufReader::new(&stream).take(256).read_line(&mut line)?;
+    let username = line.trim();
+    match issue_token(key, server_addresses.to_vec(), username) {
+        Ok(token) => {
+            let mut bytes = Vec::new();
+            token.write(&mut bytes)?;
+            stream.write_all(&bytes)?;
+            println!("token issuer: issued token for {username} (client {})", token.client_id);
+        }
+        Err(reason) => {
+            writeln!(stream, "ERR {reason}")?;
+            eprintln!("token issuer: refused {username:?}: {reason}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Chat flood protection and the slash commands players can type instead of a message.
+
+use std::time::Duration;
+
+use protocol::rate::TokenBucket;
+use protocol::{Difficulty, MapSize, MAX_CHAT_LEN};
+
+/// Lines a player may send in a burst, and how fast the allowance comes back.
+const CHAT_BURST: u32 = 5;
+const CHAT_REFILL: Duration = Duration::from_secs(2);
+
+/// Allows one chat line per `CHAT_REFILL` on average, with a small burst.
+pub type ChatLimiter = TokenBucket<CHAT_BURST, { CHAT_REFILL.as_millis() as u64 }>;
+
+/// Trims a chat line, drops control characters and cuts it to `MAX_CHAT_LEN` characters.
+pub fn clean(text: &str) -> String {
+    text.trim().chars().filter(|c| !c.is_control()).take(MAX_CHAT_LEN).collect()
+}
+
+/// A chat line starting with `/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `/ready`, `/ready off`, `/unready`
+    Ready(bool),
+    /// `/vote <difficulty>`
+    VoteDifficulty(Difficulty),
+    /// `/vote <size>`, or `/vote size <size>` for `medium`
+    VoteMapSize(MapSize),
+    /// `/vote level <number or name>`
+    VoteLevel(String),
+    /// `/levels`: the server's playlist
+    Levels,
+    /// `/who`: everyone on the server
+    Who,
+    /// `/team <text>`: a line only teammates see
+    Team(String),
+    /// `/help`
+    Help,
+}
+
+/// One line per command, sent in reply to `/help`.
+pub const HELP: [&str; 7] = [
+    "/ready [off], /unready: ready up for the next round",
+    "/vote easy|medium|hard: vote on difficulty",
+    "/vote small|large, /vote size medium: vote on maze size",
+    "/vote level <number or name>: vote on the next playlist level",
+    "/levels: list the playlist",
+    "/who: list players",
+    "/team <text>: message your team",
+];
+
+impl Command {
+    /// Parses a line without its leading `/`. The error is the reply for the player.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let (name, rest) = line.split_once(' ').map_or((line, ""), |(n, r)| (n, r.trim()));
+        let size = |name: &str| MapSize::ALL.into_iter().find(|s| s.as_str() == name);
+        match (name, rest) {
+            ("ready", "" | "on") => Ok(Command::Ready(true)),
+            ("ready", "off") | ("unready", "") => Ok(Command::Ready(false)),
+            ("vote", choice) if choice.starts_with("level ") => Ok(Command::VoteLevel(choice["level ".len()..].trim().to_string())),
+            ("vote", choice) => match choice.split_once(' ') {
+                Some(("size", name)) => size(name.trim()).map(Command::VoteMapSize),
+                Some(("difficulty", name)) => Difficulty::parse(name.trim()).map(Command::VoteDifficulty),
+                _ => Difficulty::parse(choice).map(Command::VoteDifficulty).or_else(|| size(choice).map(Command::VoteMapSize)),
+            }
+            .ok_or_else(|| format!("cannot vote for '{choice}'; try easy, hard, small or large")),
+            ("levels", "") => Ok(Command::Levels),
+            ("who", "") => Ok(Command::Who),
+            ("team", "") => Err("usage: /team <text>".to_string()),
+            ("team", text) => Ok(Command::Team(text.to_string())),
+            ("help", _) => Ok(Command::Help),
+            _ => Err(format!("unknown command /{name}; /help lists them")),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! What happens when players walk into each other. Moves are collected over a tick and
+//! resolved together, so the outcome depends on who moved where, not on packet arrival order.
+
+use std::collections::BTreeMap;
+
+use protocol::movement;
+
+/// Rule for a move into a cell another player stands in or moves into on the same tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Collision {
+    /// Players walk through each other and may share a cell.
+    Overlap,
+    /// The move is refused. Two players heading for the same cell: the lower id gets it.
+    #[default]
+    Block,
+    /// Like `Block`, but a player standing still is shoved one cell further along, if that
+    /// cell is open and free; otherwise the move is refused.
+    Push,
+}
+
+impl Collision {
+    /// Parses `overlap`, `block` or `push`, e.g. from `MAZE_COLLISION`.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "overlap" => Some(Collision::Overlap),
+            "block" => Some(Collision::Block),
+            "push" => Some(Collision::Push),
+            _ => None,
+        }
+    }
+}
+
+/// Resolves one step per player. `positions` holds every player on the map; `moves` the cell
+/// each mover wants to enter, already checked against walls. Returns where everyone ends up.
+///
+/// Players moving into each other's cells (a head-on swap) both stay put, so nobody passes
+/// through anyone in a one-wide corridor. Following a player who moves away is allowed.
+pub fn resolve(
+    rule: Collision,
+    cells: &[u8],
+    width: usize,
+    height: usize,
+    positions: &BTreeMap<u64, (usize, usize)>,
+    moves: &BTreeMap<u64, (usize, usize)>,
+) -> BTreeMap<u64, (usize, usize)> {
+    let mut pending: BTreeMap<u64, (usize, usize)> = moves
+        .iter()
+        .filter(|(id, to)| positions.get(id).is_some_and(|at| at != *to))
+        .map(|(id, to)| (*id, *to))
+        .collect();
+    if rule != Collision::Overlap {
+        // Refusing one move can block the players behind it, so repeat until nothing changes
+        loop {
+            let mut refused = Vec::new();
+            let mut pushed = None;
+            for (&id, &to) in &pending {
+                let from = positions[&id];
+                let claimed = pending.iter().any(|(other, target)| *other < id && *target == to);
+                let swap = pending.iter().find(|(other, target)| positions[other] == to && **target == from);
+                // Someone who is not leaving the cell
+                let holder = positions.iter().find(|(other, at)| **other != id && **at == to && !pending.contains_key(other));
+                if let Some((&other, _)) = swap {
+                    // Both at once, or the loser of a swap would get pushed back
+                    refused = vec![id, other];
+                } else if claimed {
+                    refused = vec![id];
+                } else if let Some((&holder, _)) = holder {
+                    match push_target(rule, cells, width, height, positions, &pending, from, to) {
+                        Some(cell) => pushed = Some((holder, cell)),
+                        None => refused = vec![id],
+                    }
+                }
+                if !refused.is_empty() || pushed.is_some() { break; }
+            }
+            match pushed {
+                _ if !refused.is_empty() => pending.retain(|id, _| !refused.contains(id)),
+                Some((holder, cell)) => { pending.insert(holder, cell); }
+                None => break,
+            }
+        }
+    }
+    let mut result = positions.clone();
+    result.extend(pending);
+    result
+}
+
+/// Cell a player at `to` is shoved into by a mover coming from `from`, if the rule pushes
+/// and that cell is open, unoccupied and nobody else is heading there.
+#[allow(clippy::too_many_arguments)]
+fn push_target(
+    rule: Collision,
+    cells: &[u8],
+    width: usize,
+    height: usize,
+    positions: &BTreeMap<u64, (usize, usize)>,
+    pending: &BTreeMap<u64, (usize, usize)>,
+    from: (usize, usize),
+    to: (usize, usize),
+) -> Option<(usize, usize)> {
+    if rule != Collision::Push { return None; }
+    let (dx, dy) = (to.0 as i32 - from.0 as i32, to.1 as i32 - from.1 as i32);
+    let cell = movement::step(cells, width, height, to, dx, dy);
+    let free = cell != to && !positions.values().any(|at| *at == cell) && !pending.values().any(|target| *target == cell);
+    free.then_some(cell)
+}
@@ -1,22 +1,92 @@
-// Gameplay modules removed from compilation to keep a minimal networking skeleton.
+//! The MazeWars game server: a Bevy app that owns the world and talks to clients over Renet.
+//! `MazeWarsServerPlugin` wires the systems together; the modules below hold the rules they apply.
+
+pub mod auth;
+pub mod chat;
+pub mod collision;
+pub mod mapfile;
+pub mod playlist;
+pub mod rules;
+pub mod visibility;
 
 use bevy_renet::renet::{ConnectionConfig, DefaultChannel, RenetServer, ServerEvent};
 use renet_netcode::{NetcodeServerTransport, ServerAuthentication, ServerConfig};
 use local_ip_address::local_ip;
 
-use bevy_ecs::prelude::{EventReader, Res, ResMut, Resource};
-use std::collections::{HashMap, HashSet};
-use rand::Rng;
+use bevy_ecs::prelude::{EventReader, EventWriter, IntoSystemConfigs, Res, ResMut, Resource};
+use bevy_time::{Fixed, Time};
+use protocol::movement::{self, Action, Motion, MovementMode, Steering, StepLimiter};
+use protocol::snapshot::{PlayerTable, SNAPSHOT_HISTORY};
+use protocol::analysis::MazeStats;
+use protocol::maze::{self, Algorithm};
+use protocol::{ClientMessage, Codec, Difficulty, Facing, LevelInfo, MapSize, PlayerState, RoundResult, RoundState, ScoreEntry, ServerFrame, TeamScore, ServerMessage, Snapshot, MAX_HEALTH, PROTOCOL_VERSION};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use collision::Collision;
+use playlist::Playlist;
+use rules::{tally, vote_winner, Contender, GameMode, RoundRules};
+use visibility::Visibility;
 
 /// Server plugin that configures Renet UDP transport and registers systems
 pub struct MazeWarsServerPlugin {
     pub bind_addr: String,
     pub max_clients: usize,
+    /// Gameplay ticks per second. Input, combat, rounds and snapshots all run on this
+    /// fixed timestep, independent of how fast the app loop spins.
+    pub tick_hz: u32,
+    /// Player snapshots sent per second over the unreliable channel.
+    pub snapshot_hz: f32,
+    /// Netcode private key. When set, only clients holding a connect token signed with it get in.
+    pub private_key: Option<auth::PrivateKey>,
+    /// TCP address of the connect-token issuer; only used together with `private_key`.
+    pub token_issuer: Option<String>,
+    /// How long a dropped player is kept for its client to resume the session.
+    pub session_grace: std::time::Duration,
+    /// Which other players each client is sent; `Visibility::Full` for casual modes.
+    /// `MAZE_VISIBILITY` (`full`, `los`, `los:<radius>`) overrides it.
+    pub visibility: Visibility,
+    /// How rounds end. `MAZE_RULES` (e.g. `score:500,last,exit,time:120`) overrides it.
+    pub round_rules: RoundRules,
+    /// Free-for-all or teams. `MAZE_MODE` (`ffa`, `teams`, `teams:<count>`, plus `:ff` for
+    /// friendly fire) overrides it.
+    pub mode: GameMode,
+    /// What happens when players walk into each other. `MAZE_COLLISION` (`overlap`, `block`,
+    /// `push`) overrides it.
+    pub collision: Collision,
+    /// Grid steps or continuous movement, announced in `Welcome`. `MAZE_MOVEMENT` (`grid`,
+    /// `continuous`) overrides it.
+    pub movement: MovementMode,
+    /// Seed of the first maze; every later one follows from it, so a whole run can be
+    /// replayed. Random for every level when unset. `MAZE_SEED` overrides it.
+    pub maze_seed: Option<u64>,
+    /// Maze algorithms, used in turn from one level to the next. `MAZE_ALGORITHMS` (a comma
+    /// separated list such as `prim,kruskal,rooms`, or `all`) overrides it.
+    pub maze_algorithms: Vec<Algorithm>,
+    /// Levels to play through instead of voting on difficulty and size. `MAZE_PLAYLIST` (the
+    /// path of a playlist file) overrides it.
+    pub playlist: Option<Playlist>,
 }
 
 impl Default for MazeWarsServerPlugin {
     fn default() -> Self {
-        Self { bind_addr: "0.0.0.0:5000".to_string(), max_clients: 32 }
+        Self {
+            bind_addr: "0.0.0.0:5000".to_string(),
+            max_clients: 32,
+            tick_hz: 30,
+            snapshot_hz: 20.0,
+            private_key: None,
+            token_issuer: None,
+            session_grace: std::time::Duration::from_secs(30),
+            visibility: Visibility::default(),
+            round_rules: RoundRules::default(),
+            mode: GameMode::default(),
+            collision: Collision::default(),
+            movement: MovementMode::default(),
+            maze_seed: None,
+            maze_algorithms: vec![Algorithm::Backtracker],
+            playlist: None,
+        }
     }
 }
 
@@ -27,131 +97,843 @@ impl bevy_app::Plugin for MazeWarsServerPlugin {
         let server = RenetServer::new(connection_config);
 
         // UDP netcode transport
-        let public_addr = self.bind_addr.parse().expect("Invalid bind address");
-        let socket = std::net::UdpSocket::bind(public_addr).expect("Failed to bind UDP socket");
+        let bind_addr: std::net::SocketAddr = self.bind_addr.parse().expect("Invalid bind address");
+        let socket = std::net::UdpSocket::bind(bind_addr).expect("Failed to bind UDP socket");
         socket.set_nonblocking(true).expect("Failed to set socket nonblocking");
+        let bound_addr = socket.local_addr().expect("Failed to read the bound address");
+
+        let authentication = match self.private_key {
+            Some(private_key) => {
+                if let Some(issuer) = &self.token_issuer {
+                    match auth::spawn_token_issuer(issuer, private_key, public_addresses(bound_addr)) {
+                        Ok(addr) => println!("token issuer listening on TCP {addr}"),
+                        Err(e) => panic!("Failed to start token issuer on {issuer}: {e}"),
+                    }
+                }
+                println!("secure mode: clients need a connect token");
+                ServerAuthentication::Secure { private_key }
+            }
+            None => ServerAuthentication::Unsecure,
+        };
+
+        let rules = round_rules(self.round_rules);
 
         let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).expect("time");
         let server_config = ServerConfig {
             current_time: now,
             max_clients: self.max_clients,
-            protocol_id: 0,
-            public_addresses: vec![public_addr],
-            authentication: ServerAuthentication::Unsecure,
+            protocol_id: PROTOCOL_VERSION.protocol_id(),
+            public_addresses: public_addresses(bound_addr),
+            authentication,
         };
         let transport = NetcodeServerTransport::new(server_config, socket).expect("Create netcode transport");
 
         app.insert_resource(server)
             .insert_resource(transport)
             .insert_resource(MaxClients(self.max_clients))
-            .insert_resource(init_world())
+            .insert_resource(BoundAddr(bound_addr))
+            .insert_resource(SecureMode(self.private_key.is_some()))
+            .insert_resource(init_world(
+                &rules,
+                game_mode(self.mode),
+                movement_mode(self.movement),
+                maze_seed(self.maze_seed),
+                maze_algorithms(&self.maze_algorithms),
+                playlist(self.playlist.clone()),
+            ))
+            .insert_resource(Rules(rules))
+            .insert_resource(RoundHistory::default())
+            .insert_resource(Connections::default())
+            .insert_resource(Time::<Fixed>::from_hz(self.tick_hz.max(1) as f64))
+            .insert_resource(TickRate(self.tick_hz))
+            .insert_resource(Snapshots::new(self.snapshot_hz))
+            .insert_resource(Sessions::new(self.session_grace))
+            .insert_resource(Interest(interest_rule(self.visibility)))
+            .insert_resource(CollisionRule(collision_rule(self.collision)))
             .insert_resource(StatsTimer(bevy_time::Timer::from_seconds(1.0, bevy_time::TimerMode::Repeating)))
             .insert_resource(LastClientCount(usize::MAX))
             .add_systems(bevy_app::Startup, print_bind_info)
-            .add_systems(bevy_app::Update, (handle_server_events, receive_client_messages, tick_rounds, log_server_stats));
+            .add_systems(bevy_app::Update, (handle_server_events, expire_sessions, drop_rejected_clients, log_server_stats).chain())
+            .add_systems(bevy_app::FixedUpdate, (advance_tick, receive_client_messages, move_players, respawn_players, tick_rounds, send_snapshots).chain());
     }
 }
 
+/// Addresses clients may dial us on. Netcode only accepts connect tokens naming one of these,
+/// so a wildcard bind also lists loopback and the machine's LAN address on the same port.
+pub fn public_addresses(bind_addr: std::net::SocketAddr) -> Vec<std::net::SocketAddr> {
+    let mut addrs = vec![bind_addr];
+    if bind_addr.ip().is_unspecified() {
+        let port = bind_addr.port();
+        addrs.push(std::net::SocketAddr::from(([127, 0, 0, 1], port)));
+        if let Ok(ip) = local_ip() {
+            addrs.push(std::net::SocketAddr::new(ip, port));
+        }
+    }
+    addrs
+}
+
 #[derive(Resource)]
 pub struct MaxClients(pub usize);
 
-fn print_bind_info(bind_info: Option<Res<MaxClients>>) {
+/// Address the UDP socket actually bound, e.g. with the port the OS picked for port 0.
+#[derive(Resource)]
+pub struct BoundAddr(pub std::net::SocketAddr);
+
+/// Configured `tick_hz`, announced in `Welcome`.
+#[derive(Resource)]
+struct TickRate(u32);
+
+fn advance_tick(mut connections: ResMut<Connections>) {
+    connections.tick += 1;
+}
+
+/// Whether netcode runs with a private key, i.e. token user data can be trusted.
+#[derive(Resource)]
+pub struct SecureMode(pub bool);
+
+/// Per-connection transport state, keyed by client id, plus the tick stamped on every
+/// outgoing frame.
+#[derive(Resource, Default)]
+struct Connections {
+    clients: HashMap<u64, Connection>,
+    /// Simulation tick, advanced once per `FixedUpdate`. Snapshots are numbered with it too.
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Connection {
+    codec: Codec,
+    /// Newest snapshot tick the client confirmed; baseline for its next delta.
+    acked_tick: Option<u64>,
+    /// Player tables recently sent to this client, i.e. the baselines it may ack. Kept per
+    /// client because each one only sees part of the world.
+    sent: VecDeque<(u64, PlayerTable)>,
+    /// Set when the Hello was refused; the client is dropped once `Rejected` had time to go out.
+    rejected_at: Option<std::time::Instant>,
+    /// Username from the client's connect token (secure mode); overrides the one in `Hello`.
+    verified_name: Option<String>,
+    /// Inputs refused so far; a well-behaved client never triggers these.
+    violations: InputViolations,
+    /// Token handed out in this connection's `Welcome`; parks the player on disconnect.
+    session: Option<u64>,
+    chat: chat::ChatLimiter,
+}
+
+impl Connections {
+    fn codec(&self, client_id: u64) -> Codec {
+        self.clients.get(&client_id).map(|c| c.codec).unwrap_or_default()
+    }
+
+    fn is_rejected(&self, client_id: u64) -> bool {
+        self.clients.get(&client_id).is_some_and(|c| c.rejected_at.is_some())
+    }
+}
+
+/// How long a rejected client stays connected so the `Rejected` reply can be delivered.
+const REJECT_GRACE: std::time::Duration = std::time::Duration::from_secs(1);
+
+fn drop_rejected_clients(mut server: ResMut<RenetServer>, connections: Res<Connections>) {
+    for (id, conn) in connections.clients.iter() {
+        if conn.rejected_at.is_some_and(|at| at.elapsed() >= REJECT_GRACE) && server.is_connected(*id) {
+            server.disconnect(*id);
+        }
+    }
+}
+
+/// Codec the server answers with for a client that asked for `requested`.
+/// `MAZE_CODEC=json` forces JSON for every client, which is handy when debugging traffic.
+fn negotiate_codec(requested: Codec) -> Codec {
+    match std::env::var("MAZE_CODEC").ok().and_then(|c| Codec::parse(&c)) {
+        Some(Codec::Json) => Codec::Json,
+        _ => requested,
+    }
+}
+
+fn print_bind_info(bind_info: Option<Res<MaxClients>>, bound: Res<BoundAddr>) {
     let ip = local_ip().map(|ip| ip.to_string()).unwrap_or_else(|_| "unknown".into());
     let max = bind_info.map(|m| m.0).unwrap_or(32);
-    println!("MazeWars server starting on UDP 0.0.0.0:5000 (local IP {ip}) | max_clients={max}");
+    println!("MazeWars server starting on UDP {} (local IP {ip}) | max_clients={max}", bound.0);
 }
 
-fn handle_server_events(mut server_events: EventReader<ServerEvent>) {
+#[allow(clippy::too_many_arguments)]
+fn handle_server_events(
+    mut server_events: EventReader<ServerEvent>,
+    mut server: ResMut<RenetServer>,
+    mut connections: ResMut<Connections>,
+    mut world: ResMut<WorldState>,
+    mut sessions: ResMut<Sessions>,
+    transport: Res<NetcodeServerTransport>,
+    secure: Res<SecureMode>,
+    rules: Res<Rules>,
+) {
     for event in server_events.read() {
         match event {
             ServerEvent::ClientConnected { client_id } => {
-                println!("client {client_id} connected");
+                // Unsecure clients pick their own user data, so it only counts in secure mode
+                let verified_name = match secure.0 {
+                    true => transport.user_data(*client_id).and_then(|data| auth::username_from_user_data(&data)),
+                    false => None,
+                };
+                // Every token we issue names its user; without one the `Hello` name would be unchecked
+                if secure.0 && verified_name.is_none() {
+                    println!("client {client_id} refused: its token carries no username");
+                    server.disconnect(*client_id);
+                    continue;
+                }
+                match &verified_name {
+                    Some(name) => println!("client {client_id} connected (token for {name})"),
+                    None => println!("client {client_id} connected"),
+                }
+                connections.clients.insert(*client_id, Connection { verified_name, ..Default::default() });
             }
             ServerEvent::ClientDisconnected { client_id, reason } => {
                 println!("client {client_id} disconnected: {reason}");
+                let session = connections.clients.remove(client_id).and_then(|c| c.session);
+                // Dropping the player frees their cell for `find_unused_spawn`
+                if let Some(player) = world.players.remove(client_id) {
+                    println!("{} left the game", player.name);
+                    let left = ServerMessage::PlayerLeft { id: *client_id, reason: reason.to_string() };
+                    broadcast_message(&mut server, &connections, &left);
+                    broadcast_scoreboard(&mut server, &connections, &world);
+                    broadcast_lobby(&mut server, &connections, &world, &rules.0);
+                    if let Some(token) = session {
+                        sessions.park(token, player);
+                    }
+                }
             }
         }
     }
 }
 
-fn receive_client_messages(mut server: ResMut<RenetServer>, mut world: ResMut<WorldState>) {
+#[allow(clippy::too_many_arguments)]
+fn receive_client_messages(
+    mut server: ResMut<RenetServer>,
+    mut connections: ResMut<Connections>,
+    mut sessions: ResMut<Sessions>,
+    snapshots: Res<Snapshots>,
+    mut world: ResMut<WorldState>,
+    interest: Res<Interest>,
+    tick_rate: Res<TickRate>,
+    history: Res<RoundHistory>,
+    rules: Res<Rules>,
+) {
     let client_ids: Vec<u64> = server.clients_id();
     for client_id in client_ids.into_iter() {
-        while let Some(bytes) = server.receive_message(client_id, DefaultChannel::ReliableOrdered) {
-            let parsed: Result<serde_json::Value, _> = serde_json::from_slice(&bytes);
-            let Ok(value) = parsed else { continue };
-            let event = value.get("event").and_then(|v| v.as_str()).unwrap_or("");
-            let body = value.get("body").cloned().unwrap_or(serde_json::Value::Null);
-
-            match event {
-                "Hello" | "hello" => {
-                    // Reply with a welcome message so client can confirm connectivity
-                    let name = body.get("name").and_then(|v| v.as_str()).unwrap_or("player");
-                    let reply = serde_json::json!({
-                        "event": "Welcome",
-                        "body": { "message": format!("Welcome, {}!", name), "client_id": client_id },
-                    });
-                    if let Ok(bytes) = serde_json::to_vec(&reply) {
-                        server.send_message(client_id, DefaultChannel::ReliableOrdered, bytes);
+        for channel in [DefaultChannel::ReliableOrdered, DefaultChannel::Unreliable].map(u8::from) {
+            while let Some(bytes) = server.receive_message(client_id, channel) {
+                if connections.is_rejected(client_id) { continue; }
+                let message = match ClientMessage::from_bytes(&bytes) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        eprintln!("client {client_id}: rejected message: {err}");
+                        if let Some(conn) = connections.clients.get_mut(&client_id) { conn.violations.malformed += 1; }
+                        continue;
                     }
+                };
 
-                    // Register player if new and spawn at a unique empty cell
-                    let (sx, sy) = find_unused_spawn(&world);
-                    let newp = Player { id: client_id, x: sx, y: sy };
-                    world.players.entry(client_id).or_insert(newp);
-
-                    // Send map snapshot
-                    let map_msg = serde_json::json!({
-                        "event": "Map",
-                        "body": {
-                            "width": world.map_width,
-                            "height": world.map_height,
-                            "cells": world.map_cells,
+                match message {
+                    ClientMessage::Hello { version, build, name, codec, resume } => {
+                        if version != PROTOCOL_VERSION {
+                            let reason = format!("server speaks protocol {PROTOCOL_VERSION}, client sent {version}");
+                            println!("client {client_id} rejected ({build}): {reason}");
+                            // JSON so that clients of any version can read it
+                            let reply = ServerMessage::Rejected { reason }.to_bytes(Codec::Json);
+                            server.send_message(client_id, DefaultChannel::ReliableOrdered, reply);
+                            connections.clients.entry(client_id).or_default().rejected_at = Some(std::time::Instant::now());
+                            continue;
                         }
-                    });
-                    if let Ok(bytes) = serde_json::to_vec(&map_msg) {
-                        server.send_message(client_id, DefaultChannel::ReliableOrdered, bytes);
-                    }
 
-                    // Send player's initial position
-                    if let Some(p) = world.players.get(&client_id) {
-                        let init_msg = serde_json::json!({
-                            "event": "PlayerInit",
-                            "body": {"id": p.id, "x": p.x, "y": p.y},
+                        // Settle the codec first; the Welcome itself already uses it
+                        let codec = negotiate_codec(codec);
+                        let conn = connections.clients.entry(client_id).or_default();
+                        conn.codec = codec;
+                        let verified_name = conn.verified_name.clone();
+
+                        // A repeated Hello on a live connection keeps the player it already has
+                        let is_new = !world.players.contains_key(&client_id);
+                        let resumed = resume
+                            .filter(|_| is_new)
+                            .and_then(|token| sessions.resume(token, verified_name.as_deref()));
+                        let was_resumed = resumed.is_some();
+                        let player = is_new.then(|| match resumed {
+                            Some(mut p) => {
+                                // New connection, new id and input stream; position and name carry over
+                                p.id = client_id;
+                                p.last_input = 0;
+                                p.limiter = StepLimiter::default();
+                                p.moves.clear();
+                                p.steering = Steering::default();
+                                settle_resumed(&world, &mut p);
+                                p
+                            }
+                            None => {
+                                let team = balanced_team(&world);
+                                let (sx, sy) = find_unused_spawn(&world, spawn_anchor(&world, team));
+                                let name = verified_name.unwrap_or(name);
+                                let facing = movement::open_facing(&world.map_cells, world.map_width, world.map_height, (sx, sy));
+                                let mut p = Player::new(client_id, name, team, (sx, sy), facing);
+                                if world.movement == MovementMode::Continuous {
+                                    p.motion = Some(Motion::centered((sx, sy), facing));
+                                }
+                                p
+                            }
                         });
-                        if let Ok(bytes) = serde_json::to_vec(&init_msg) {
-                            server.send_message(client_id, DefaultChannel::ReliableOrdered, bytes);
+                        let name = match &player {
+                            Some(p) => p.name.clone(),
+                            None => world.players[&client_id].name.clone(),
+                        };
+
+                        let session = rand::random::<u64>();
+                        connections.clients.entry(client_id).or_default().session = Some(session);
+                        match was_resumed {
+                            true => println!("client {client_id} resumed session of {name} ({} codec, {build})", codec.as_str()),
+                            false => println!("client {client_id} joined as {name} ({} codec, {build})", codec.as_str()),
                         }
-                    }
 
-                    // Broadcast players snapshot to everyone
-                    broadcast_players(&mut server, &world);
-                }
-                "Input" => {
-                    let dx = body.get("dx").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
-                    let dy = body.get("dy").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
-                    let mw = world.map_width; let mh = world.map_height; let cells_ptr: *const u8 = world.map_cells.as_ptr();
-                    if let Some(p) = world.players.get_mut(&client_id) {
-                        let nx = (p.x as i32 + dx).max(0) as usize;
-                        let ny = (p.y as i32 + dy).max(0) as usize;
-                        if nx < mw && ny < mh {
-                            let idx = ny * mw + nx;
-                            // SAFETY: idx bounds checked; read-only
-                            let passable = unsafe { *cells_ptr.add(idx) } == 0;
-                            if passable {
-                                p.x = nx;
-                                p.y = ny;
-                                broadcast_players(&mut server, &world);
+                        // Reply with a welcome message so client can confirm connectivity
+                        let message = format!("Welcome{}, {}!", if was_resumed { " back" } else { "" }, name);
+                        let welcome = ServerMessage::Welcome { message, client_id, codec, session, resumed: was_resumed, tick_hz: tick_rate.0, movement: world.movement };
+                        send_message(&mut server, &connections, client_id, &welcome);
+
+                        if let Some(player) = player {
+                            world.players.insert(client_id, player);
+                            broadcast_message(&mut server, &connections, &ServerMessage::PlayerJoined { id: client_id, name });
+                            broadcast_scoreboard(&mut server, &connections, &world);
+                        }
+                        broadcast_lobby(&mut server, &connections, &world, &rules.0);
+
+                        // Send map snapshot and where the round stands
+                        send_message(&mut server, &connections, client_id, &map_message(&world));
+                        send_message(&mut server, &connections, client_id, &round_message(&world));
+
+                        // Send player's initial position; everyone else learns it from the next snapshot
+                        if let Some(p) = world.players.get(&client_id) {
+                            send_message(&mut server, &connections, client_id, &ServerMessage::PlayerInit(p.state()));
+                        }
+                    }
+                    ClientMessage::Input { seq, action } => {
+                        let grid = world.movement == MovementMode::Grid;
+                        let (Some(p), Some(conn)) = (world.players.get_mut(&client_id), connections.clients.get_mut(&client_id)) else { continue };
+                        // Echoed in snapshots, refused inputs included, so the client can drop it from its replay queue
+                        p.last_input = p.last_input.max(seq);
+                        if !p.is_alive() { continue; }
+                        if !p.limiter.try_take() {
+                            conn.violations.too_fast += 1;
+                            continue;
+                        }
+                        // Applied by `move_players` once everyone's input for this tick is in
+                        if grid { p.moves.push_back(action); }
+                    }
+                    ClientMessage::Steer { seq, steering } => {
+                        let continuous = world.movement == MovementMode::Continuous;
+                        let Some(p) = world.players.get_mut(&client_id) else { continue };
+                        p.last_input = p.last_input.max(seq);
+                        if continuous { p.steering = steering.clamped(); }
+                    }
+                    ClientMessage::Shoot => {
+                        let Some(p) = world.players.get_mut(&client_id) else { continue };
+                        let now = std::time::Instant::now();
+                        if !p.is_alive() || p.next_shot_at.is_some_and(|at| now < at) { continue; }
+                        let shooter = (p.x, p.y);
+                        let (to, target) = trace_shot(&world, client_id);
+                        // Cooling down, and the tracer still travelling, both block the next shot
+                        let flight = SHOT_FLIGHT_PER_CELL * (to.0.abs_diff(shooter.0) + to.1.abs_diff(shooter.1)) as u32;
+                        if let Some(p) = world.players.get_mut(&client_id) {
+                            p.next_shot_at = Some(now + FIRE_COOLDOWN.max(flight));
+                        }
+                        broadcast_shot(&mut server, &connections, &world, &interest, client_id, shooter, to);
+                        if let Some(target) = target {
+                            hit_player(&mut server, &connections, &mut world, client_id, target);
+                        }
+                    }
+                    // Readying up and voting are for the lobby only, as with `/ready` and `/vote`
+                    ClientMessage::Ready { .. } | ClientMessage::VoteDifficulty { .. } | ClientMessage::VoteMapSize { .. } | ClientMessage::VoteLevel { .. }
+                        if world.round_state != RoundState::Lobby => {}
+                    ClientMessage::Ready { ready } => {
+                        let Some(p) = world.players.get_mut(&client_id) else { continue };
+                        p.ready = ready;
+                        broadcast_lobby(&mut server, &connections, &world, &rules.0);
+                    }
+                    ClientMessage::VoteDifficulty { difficulty } => {
+                        if world.playlist.is_some() { continue; }
+                        let Some(p) = world.players.get_mut(&client_id) else { continue };
+                        p.difficulty_vote = Some(difficulty);
+                        broadcast_lobby(&mut server, &connections, &world, &rules.0);
+                    }
+                    ClientMessage::VoteMapSize { size } => {
+                        if world.playlist.is_some() { continue; }
+                        let Some(p) = world.players.get_mut(&client_id) else { continue };
+                        p.size_vote = Some(size);
+                        broadcast_lobby(&mut server, &connections, &world, &rules.0);
+                    }
+                    ClientMessage::VoteLevel { level } => {
+                        let Some(index) = votable_level(&world, &level.to_string()) else { continue };
+                        let Some(p) = world.players.get_mut(&client_id) else { continue };
+                        p.level_vote = Some(index);
+                        broadcast_lobby(&mut server, &connections, &world, &rules.0);
+                    }
+                    ClientMessage::Chat { text, team_only } => {
+                        let text = chat::clean(&text);
+                        let Some(conn) = connections.clients.get_mut(&client_id) else { continue };
+                        if text.is_empty() || !world.players.contains_key(&client_id) { continue; }
+                        if !conn.chat.try_take() {
+                            conn.violations.chat_flood += 1;
+                            reply(&mut server, &connections, client_id, "slow down, you are sending messages too fast");
+                            continue;
+                        }
+                        match text.strip_prefix('/') {
+                            Some(line) => run_command(&mut server, &connections, &mut world, &rules.0, client_id, line),
+                            None => send_chat(&mut server, &connections, &world, client_id, text, team_only),
+                        }
+                    }
+                    ClientMessage::QueryHistory => {
+                        let rounds = history.0.iter().cloned().collect();
+                        send_message(&mut server, &connections, client_id, &ServerMessage::History { rounds });
+                    }
+                    ClientMessage::Ack { tick } => {
+                        // Acks arrive unreliably: ignore reordered ones and ticks we never sent
+                        if let Some(conn) = connections.clients.get_mut(&client_id) {
+                            if tick <= snapshots.last_tick && conn.acked_tick.is_none_or(|t| tick > t) {
+                                conn.acked_tick = Some(tick);
                             }
                         }
                     }
                 }
-                _ => {}
             }
         }
     }
 }
 
+// ---------------- Chat -----------------
+
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Sends `sender`'s line to everyone, or with `team_only` to their teammates.
+fn send_chat(server: &mut RenetServer, connections: &Connections, world: &WorldState, sender: u64, text: String, team_only: bool) {
+    let Some(p) = world.players.get(&sender) else { return };
+    if team_only && p.team.is_none() {
+        return reply(server, connections, sender, "there are no teams in this mode");
+    }
+    println!("{}{}: {text}", if team_only { "[team] " } else { "" }, p.name);
+    let message = ServerMessage::Chat { from: Some(sender), name: p.name.clone(), text, team_only, sent_at_ms: unix_millis() };
+    if team_only {
+        for mate in world.players.values().filter(|m| m.team == p.team) {
+            send_message(server, connections, mate.id, &message);
+        }
+    } else {
+        broadcast_message(server, connections, &message);
+    }
+}
+
+/// Server line to one client, e.g. a command's output.
+fn reply(server: &mut RenetServer, connections: &Connections, client_id: u64, text: impl Into<String>) {
+    let message = ServerMessage::Chat { from: None, name: "server".to_string(), text: text.into(), team_only: false, sent_at_ms: unix_millis() };
+    send_message(server, connections, client_id, &message);
+}
+
+fn run_command(server: &mut RenetServer, connections: &Connections, world: &mut WorldState, rules: &RoundRules, client_id: u64, line: &str) {
+    let command = match chat::Command::parse(line) {
+        Ok(command) => command,
+        Err(error) => return reply(server, connections, client_id, error),
+    };
+    let in_lobby = world.round_state == RoundState::Lobby;
+    let level_vote = match &command {
+        chat::Command::VoteLevel(choice) => votable_level(world, choice),
+        _ => None,
+    };
+    let has_playlist = world.playlist.is_some();
+    let Some(p) = world.players.get_mut(&client_id) else { return };
+    match command {
+        chat::Command::Ready(_) | chat::Command::VoteDifficulty(_) | chat::Command::VoteMapSize(_) | chat::Command::VoteLevel(_) if !in_lobby => {
+            reply(server, connections, client_id, "only possible in the lobby");
+        }
+        chat::Command::VoteDifficulty(_) | chat::Command::VoteMapSize(_) if has_playlist => {
+            reply(server, connections, client_id, "levels come from the playlist; /levels lists them");
+        }
+        chat::Command::Ready(ready) => {
+            p.ready = ready;
+            broadcast_lobby(server, connections, world, rules);
+        }
+        chat::Command::VoteDifficulty(difficulty) => {
+            p.difficulty_vote = Some(difficulty);
+            broadcast_lobby(server, connections, world, rules);
+        }
+        chat::Command::VoteMapSize(size) => {
+            p.size_vote = Some(size);
+            broadcast_lobby(server, connections, world, rules);
+        }
+        chat::Command::VoteLevel(choice) => match (level_vote, world.playlist.as_ref().map(|l| l.order)) {
+            (Some(index), _) => {
+                p.level_vote = Some(index);
+                broadcast_lobby(server, connections, world, rules);
+            }
+            (None, Some(playlist::Order::Vote)) => reply(server, connections, client_id, format!("no level '{choice}'; /levels lists them")),
+            (None, Some(_)) => reply(server, connections, client_id, "levels follow the playlist's order"),
+            (None, None) => reply(server, connections, client_id, "this server has no playlist"),
+        },
+        chat::Command::Levels => match &world.playlist {
+            Some(playlist) => {
+                let current = playlist.current().map(|(i, _)| i);
+                for (i, level) in playlist.levels.iter().enumerate() {
+                    let marker = if Some(i) == current { " (playing)" } else { "" };
+                    reply(server, connections, client_id, format!("{}. {}{marker}", i + 1, level.name));
+                }
+            }
+            None => reply(server, connections, client_id, "this server has no playlist"),
+        },
+        chat::Command::Who => {
+            let mut players: Vec<&Player> = world.players.values().collect();
+            players.sort_by_key(|p| (p.team, p.name.clone()));
+            let names: Vec<String> = players
+                .iter()
+                .map(|p| match p.team {
+                    Some(team) => format!("{} (team {})", p.name, team + 1),
+                    None => p.name.clone(),
+                })
+                .collect();
+            reply(server, connections, client_id, format!("{} online: {}", names.len(), names.join(", ")));
+        }
+        chat::Command::Team(text) => send_chat(server, connections, world, client_id, text, true),
+        chat::Command::Help => {
+            for line in chat::HELP {
+                reply(server, connections, client_id, line);
+            }
+        }
+    }
+}
+
+/// Index of the playlist level `choice` names, if the playlist advances by vote.
+fn votable_level(world: &WorldState, choice: &str) -> Option<usize> {
+    let playlist = world.playlist.as_ref().filter(|l| l.order == playlist::Order::Vote)?;
+    playlist.find(choice)
+}
+
+// ---------------- Sessions -----------------
+
+/// Fits a player taken back from `Sessions` into the current level. A new level may have
+/// started while they were away: a team the mode no longer has is dealt again, and a cell
+/// that is now off the map, a wall or taken is swapped for a fresh spawn.
+fn settle_resumed(world: &WorldState, p: &mut Player) {
+    let team_fits = match world.mode.teams() {
+        None => p.team.is_none(),
+        Some(teams) => p.team.is_some_and(|team| team < teams),
+    };
+    if !team_fits {
+        p.team = balanced_team(world);
+    }
+    let (w, h) = (world.map_width, world.map_height);
+    let cell_fits = p.x < w && p.y < h && cell_is_open(&world.map_cells, w, p.x, p.y) && !is_occupied(&world.players, p.x, p.y);
+    if !cell_fits {
+        (p.x, p.y) = find_unused_spawn(world, spawn_anchor(world, p.team));
+        p.facing = movement::open_facing(&world.map_cells, w, h, (p.x, p.y));
+    }
+    // Recentred on the new cell, if it changed
+    p.motion = p.motion();
+}
+
+/// Players whose connection dropped, kept for `grace` so a reconnecting client can reclaim
+/// them with the session token from its last `Welcome`.
+#[derive(Resource)]
+struct Sessions {
+    grace: std::time::Duration,
+    parked: HashMap<u64, (Player, std::time::Instant)>,
+}
+
+impl Sessions {
+    fn new(grace: std::time::Duration) -> Self {
+        Self { grace, parked: HashMap::new() }
+    }
+
+    fn park(&mut self, token: u64, player: Player) {
+        self.parked.insert(token, (player, std::time::Instant::now()));
+    }
+
+    /// Takes the player parked under `token`. In secure mode the token's verified name must
+    /// match too, so a leaked session token cannot move someone else's player.
+    fn resume(&mut self, token: u64, verified_name: Option<&str>) -> Option<Player> {
+        let (player, parked_at) = self.parked.get(&token)?;
+        if parked_at.elapsed() > self.grace || verified_name.is_some_and(|name| name != player.name) {
+            return None;
+        }
+        self.parked.remove(&token).map(|(player, _)| player)
+    }
+}
+
+fn expire_sessions(mut sessions: ResMut<Sessions>) {
+    let grace = sessions.grace;
+    sessions.parked.retain(|_, (player, parked_at)| {
+        let keep = parked_at.elapsed() <= grace;
+        if !keep {
+            println!("session of {} expired", player.name);
+        }
+        keep
+    });
+}
+
+// ---------------- Movement -----------------
+
+/// Collision rule applied by `move_players`.
+#[derive(Resource)]
+struct CollisionRule(Collision);
+
+fn movement_mode(configured: MovementMode) -> MovementMode {
+    match std::env::var("MAZE_MOVEMENT") {
+        Ok(v) => MovementMode::parse(&v).unwrap_or_else(|| {
+            eprintln!("ignoring MAZE_MOVEMENT={v}: expected grid or continuous");
+            configured
+        }),
+        Err(_) => configured,
+    }
+}
+
+fn collision_rule(configured: Collision) -> Collision {
+    match std::env::var("MAZE_COLLISION") {
+        Ok(v) => Collision::parse(&v).unwrap_or_else(|| {
+            eprintln!("ignoring MAZE_COLLISION={v}: expected overlap, block or push");
+            configured
+        }),
+        Err(_) => configured,
+    }
+}
+
+/// Applies the actions received this tick. Every player's first action is resolved together,
+/// then every second one, and so on, so collisions do not depend on which packet came first.
+fn move_players(mut world: ResMut<WorldState>, rule: Res<CollisionRule>, time: Res<Time<Fixed>>) {
+    if world.movement == MovementMode::Continuous {
+        return steer_players(&mut world, rule.0, time.timestep().as_secs_f32());
+    }
+    let WorldState { map_cells, map_width, map_height, players, .. } = &mut *world;
+    while players.values().any(|p| !p.moves.is_empty()) {
+        let mut moves = BTreeMap::new();
+        for p in players.values_mut() {
+            let Some(action) = p.moves.pop_front() else { continue };
+            if !p.is_alive() { continue; }
+            let pose = movement::apply(map_cells, *map_width, *map_height, p.pose(), action);
+            p.facing = pose.facing;
+            moves.insert(p.id, (pose.x, pose.y));
+        }
+        let positions = players.values().filter(|p| p.is_alive()).map(|p| (p.id, (p.x, p.y))).collect();
+        let resolved = collision::resolve(rule.0, map_cells, *map_width, *map_height, &positions, &moves);
+        for (id, (x, y)) in resolved {
+            if let Some(p) = players.get_mut(&id) {
+                (p.x, p.y) = (x, y);
+            }
+        }
+    }
+}
+
+/// Continuous movement: integrates every player's steering over one tick. Cell changes go
+/// through the collision rule like grid steps; a refused one undoes the player's move.
+fn steer_players(world: &mut WorldState, rule: Collision, dt: f32) {
+    let WorldState { map_cells, map_width, map_height, players, .. } = world;
+    let mut moved = HashMap::new();
+    let mut moves = BTreeMap::new();
+    for p in players.values().filter(|p| p.is_alive()) {
+        let Some(motion) = p.motion() else { continue };
+        let next = movement::integrate(map_cells, *map_width, *map_height, motion, p.steering, dt);
+        moved.insert(p.id, next);
+        moves.insert(p.id, next.cell());
+    }
+    let positions = players.values().filter(|p| p.is_alive()).map(|p| (p.id, (p.x, p.y))).collect();
+    let resolved = collision::resolve(rule, map_cells, *map_width, *map_height, &positions, &moves);
+    for (id, cell) in resolved {
+        let Some(p) = players.get_mut(&id) else { continue };
+        match moved.get(&id) {
+            Some(next) if next.cell() == cell => p.motion = Some(*next),
+            // Refused: keep the turn, drop the move
+            Some(next) => p.motion = p.motion().map(|m| Motion { yaw: next.yaw, ..m }),
+            None => {}
+        }
+        (p.x, p.y) = cell;
+        if let Some(m) = p.motion { p.facing = m.facing(); }
+    }
+}
+
+// ---------------- Shooting -----------------
+
+/// Minimum time between two shots of one player.
+const FIRE_COOLDOWN: std::time::Duration = std::time::Duration::from_millis(500);
+/// Time the tracer takes per cell; a shot counts as in flight until it reaches its end.
+const SHOT_FLIGHT_PER_CELL: std::time::Duration = std::time::Duration::from_millis(25);
+
+/// Walks from the shooter along its facing until a wall. Returns the last cell reached and
+/// the first other player standing in the way, if any.
+fn trace_shot(world: &WorldState, shooter: u64) -> ((usize, usize), Option<u64>) {
+    let Some(p) = world.players.get(&shooter) else { return ((0, 0), None) };
+    let (dx, dy) = p.facing.delta();
+    let mut at = (p.x, p.y);
+    loop {
+        let next = movement::step(&world.map_cells, world.map_width, world.map_height, at, dx, dy);
+        if next == at {
+            return (at, None);
+        }
+        at = next;
+        if let Some(hit) = world.players.values().find(|o| o.id != shooter && o.is_alive() && (o.x, o.y) == at) {
+            return (at, Some(hit.id));
+        }
+    }
+}
+
+/// Sends `ShotFired` to every client that can see either end of the tracer, so shooting
+/// does not reveal hidden players to the whole server.
+fn broadcast_shot(
+    server: &mut RenetServer,
+    connections: &Connections,
+    world: &WorldState,
+    interest: &Interest,
+    shooter: u64,
+    from: (usize, usize),
+    to: (usize, usize),
+) {
+    let message = ServerMessage::ShotFired { shooter, from, to };
+    for viewer in world.players.values() {
+        let at = (viewer.x, viewer.y);
+        let sees = |cell| interest.0.can_see(at, cell, &world.map_cells, world.map_width);
+        if viewer.id == shooter || sees(from) || sees(to) {
+            send_message(server, connections, viewer.id, &message);
+        }
+    }
+}
+
+// ---------------- Health and scoring -----------------
+
+/// Health one shot takes.
+const SHOT_DAMAGE: u32 = 1;
+const SCORE_PER_HIT: i32 = 10;
+const SCORE_PER_KILL: i32 = 100;
+/// How long a killed player stays out before respawning.
+const RESPAWN_DELAY: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Applies one shot's damage, and on a kill the kill/death counts and the respawn timer.
+fn hit_player(server: &mut RenetServer, connections: &Connections, world: &mut WorldState, shooter: u64, target: u64) {
+    let shooter_team = world.players.get(&shooter).and_then(|p| p.team);
+    let Some(t) = world.players.get_mut(&target) else { return };
+    // Teammates block the shot; with friendly fire they take the damage, but nobody scores
+    if !world.mode.can_damage(shooter_team, t.team) { return; }
+    let friendly = shooter_team.is_some() && shooter_team == t.team;
+    t.health = t.health.saturating_sub(SHOT_DAMAGE);
+    let health = t.health;
+    let killed = health == 0;
+    if killed {
+        t.deaths += 1;
+        t.respawn_at = Some(std::time::Instant::now() + RESPAWN_DELAY);
+    }
+    if let Some(s) = world.players.get_mut(&shooter).filter(|_| !friendly) {
+        s.score += SCORE_PER_HIT;
+        if killed {
+            s.kills += 1;
+            s.score += SCORE_PER_KILL;
+        }
+    }
+    broadcast_message(server, connections, &ServerMessage::PlayerHit { shooter, target, health });
+    if killed {
+        println!("client {shooter} killed {target}");
+        broadcast_message(server, connections, &ServerMessage::PlayerKilled { killer: shooter, victim: target });
+    }
+    broadcast_scoreboard(server, connections, world);
+}
+
+/// Brings back players whose respawn delay ran out, away from everyone still alive.
+fn respawn_players(mut server: ResMut<RenetServer>, connections: Res<Connections>, mut world: ResMut<WorldState>, rules: Res<Rules>) {
+    // Under elimination the dead sit out the rest of the round
+    if rules.0.last_standing && world.round_state == RoundState::InRound { return; }
+    let now = std::time::Instant::now();
+    let due: Vec<u64> = world.players.values().filter(|p| p.respawn_at.is_some_and(|at| at <= now)).map(|p| p.id).collect();
+    for id in due {
+        let pos = match world.players.get(&id).and_then(|p| p.team) {
+            Some(team) => find_unused_spawn(&world, spawn_anchor(&world, Some(team))),
+            None => find_spawn_away_from_enemies(&world, id),
+        };
+        let facing = movement::open_facing(&world.map_cells, world.map_width, world.map_height, pos);
+        let Some(p) = world.players.get_mut(&id) else { continue };
+        (p.x, p.y, p.facing) = (pos.0, pos.1, facing);
+        p.health = MAX_HEALTH;
+        p.respawn_at = None;
+        println!("{} respawned at {:?}", p.name, pos);
+        // The client's prediction restarts from here, like after joining
+        send_message(&mut server, &connections, id, &ServerMessage::PlayerInit(p.state()));
+    }
+}
+
+/// Current standings, best score first.
+fn standings(world: &WorldState) -> Vec<ScoreEntry> {
+    let mut entries: Vec<ScoreEntry> = world
+        .players
+        .values()
+        .map(|p| ScoreEntry { id: p.id, name: p.name.clone(), team: p.team, kills: p.kills, deaths: p.deaths, score: p.score })
+        .collect();
+    entries.sort_by(|a, b| b.score.cmp(&a.score).then(b.kills.cmp(&a.kills)).then(a.id.cmp(&b.id)));
+    entries
+}
+
+/// Per-team totals, best score first; empty in free-for-all.
+fn team_standings(world: &WorldState) -> Vec<TeamScore> {
+    let Some(teams) = world.mode.teams() else { return Vec::new() };
+    let mut totals: Vec<TeamScore> = (0..teams)
+        .map(|team| TeamScore { team, players: 0, kills: 0, deaths: 0, score: 0 })
+        .collect();
+    for p in world.players.values() {
+        let Some(total) = p.team.and_then(|team| totals.get_mut(team as usize)) else { continue };
+        total.players += 1;
+        total.kills += p.kills;
+        total.deaths += p.deaths;
+        total.score += p.score;
+    }
+    totals.sort_by(|a, b| b.score.cmp(&a.score).then(a.team.cmp(&b.team)));
+    totals
+}
+
+fn scoreboard_message(world: &WorldState) -> ServerMessage {
+    ServerMessage::Scoreboard { entries: standings(world), teams: team_standings(world) }
+}
+
+fn broadcast_scoreboard(server: &mut RenetServer, connections: &Connections, world: &WorldState) {
+    broadcast_message(server, connections, &scoreboard_message(world));
+}
+
+// ---------------- Input validation -----------------
+
+/// Per-client counts of refused inputs, logged by `log_server_stats` for abuse detection.
+#[derive(Default)]
+struct InputViolations {
+    /// Messages that did not decode, e.g. unknown actions or hand-crafted fields.
+    malformed: u32,
+    /// Faster than the step rate allows.
+    too_fast: u32,
+    /// Chat lines dropped by flood protection.
+    chat_flood: u32,
+    /// Total at the last log line, so each line only reports something new.
+    reported: u32,
+}
+
+impl InputViolations {
+    fn total(&self) -> u32 {
+        self.malformed + self.too_fast + self.chat_flood
+    }
+}
+
+fn send_message(server: &mut RenetServer, connections: &Connections, client_id: u64, message: &ServerMessage) {
+    let bytes = frame(connections, message).to_bytes(connections.codec(client_id));
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, bytes);
+}
+
+fn broadcast_message(server: &mut RenetServer, connections: &Connections, message: &ServerMessage) {
+    // Encode at most once per codec in use
+    let frame = frame(connections, message);
+    let mut encoded: HashMap<Codec, Vec<u8>> = HashMap::new();
+    for id in server.clients_id() {
+        let codec = connections.codec(id);
+        let bytes = encoded.entry(codec).or_insert_with(|| frame.to_bytes(codec)).clone();
+        server.send_message(id, DefaultChannel::ReliableOrdered, bytes);
+    }
+}
+
+fn frame(connections: &Connections, message: &ServerMessage) -> ServerFrame {
+    ServerFrame { tick: connections.tick, message: message.clone() }
+}
+
 #[derive(Resource)]
 struct StatsTimer(pub bevy_time::Timer);
 
@@ -164,6 +946,7 @@ fn log_server_stats(
     server: Res<RenetServer>,
     max_clients: Option<Res<MaxClients>>,
     mut last: ResMut<LastClientCount>,
+    mut connections: ResMut<Connections>,
 ) {
     timer.0.tick(time.delta());
     if timer.0.just_finished() {
@@ -173,18 +956,88 @@ fn log_server_stats(
             let max = max_clients.map(|m| m.0).unwrap_or(32);
             println!("clients: {connected}/{max}");
         }
+        for (id, conn) in connections.clients.iter_mut() {
+            let v = &mut conn.violations;
+            if v.total() > v.reported {
+                eprintln!(
+                    "client {id}: {} rejected inputs since last report (total: malformed {}, too fast {}, chat flood {})",
+                    v.total() - v.reported, v.malformed, v.too_fast, v.chat_flood
+                );
+                v.reported = v.total();
+            }
+        }
     }
 }
 
 pub fn run_server(bind_addr: Option<String>, max_clients: Option<usize>) {
-    let plugin = MazeWarsServerPlugin { bind_addr: bind_addr.unwrap_or_else(|| "0.0.0.0:5000".into()), max_clients: max_clients.unwrap_or(32) };
+    let plugin = MazeWarsServerPlugin {
+        bind_addr: bind_addr.unwrap_or_else(|| "0.0.0.0:5000".into()),
+        max_clients: max_clients.unwrap_or(32),
+        ..Default::default()
+    };
+    run(plugin);
+}
+
+/// Runs the server headless with a fully configured plugin, e.g. one in secure mode.
+pub fn run(plugin: MazeWarsServerPlugin) {
+    app(plugin).run();
+}
 
-    bevy_app::App::new()
-        .add_plugins(bevy_app::ScheduleRunnerPlugin::default())
-        .add_plugins(bevy_time::TimePlugin::default())
+fn app(plugin: MazeWarsServerPlugin) -> bevy_app::App {
+    // One frame per tick instead of spinning; `FixedUpdate` catches up if a frame runs late
+    let frame = std::time::Duration::from_secs_f64(1.0 / plugin.tick_hz.max(1) as f64);
+    let mut app = bevy_app::App::new();
+    app.add_plugins(bevy_app::ScheduleRunnerPlugin::run_loop(frame))
+        .add_plugins(bevy_time::TimePlugin)
         .add_plugins(bevy_renet::RenetServerPlugin)
-        .add_plugins(plugin)
-        .run();
+        .add_plugins(bevy_renet::netcode::NetcodeServerPlugin)
+        .add_plugins(plugin);
+    app
+}
+
+/// A server running on its own thread, e.g. in tests. Dropping it stops the server.
+pub struct ServerHandle {
+    /// Address the server is bound to; with port 0 in `bind_addr`, the port the OS picked.
+    pub addr: std::net::SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+/// Starts the server on its own thread. Returns once the socket is bound, so clients may
+/// connect right away.
+pub fn spawn(plugin: MazeWarsServerPlugin) -> ServerHandle {
+    let stop = Arc::new(AtomicBool::new(false));
+    let (bound_tx, bound_rx) = std::sync::mpsc::channel();
+    let thread = std::thread::spawn({
+        let stop = stop.clone();
+        move || {
+            let mut app = app(plugin);
+            app.insert_resource(StopFlag(stop)).add_systems(bevy_app::Last, exit_when_stopped);
+            let _ = bound_tx.send(app.world().resource::<BoundAddr>().0);
+            app.run();
+        }
+    });
+    let addr = bound_rx.recv().expect("server thread failed to start");
+    ServerHandle { addr, stop, thread: Some(thread) }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Set by `ServerHandle` to shut its server down.
+#[derive(Resource)]
+struct StopFlag(Arc<AtomicBool>);
+
+fn exit_when_stopped(stop: Res<StopFlag>, mut exit: EventWriter<bevy_app::AppExit>) {
+    if stop.0.load(Ordering::Relaxed) {
+        exit.send(bevy_app::AppExit::Success);
+    }
 }
 
 // ---------------- World / Map -----------------
@@ -194,64 +1047,182 @@ struct WorldState {
     map_width: usize,
     map_height: usize,
     map_cells: Vec<u8>, // 0 passage, 1 wall
+    map_size: MapSize,
     spawn_x: usize,
     spawn_y: usize,
+    /// Spawn markers of a handcrafted map; empty for generated mazes, where players spawn
+    /// on any open cell.
+    spawn_points: Vec<(usize, usize)>,
     players: HashMap<u64, Player>,
+    /// Goal cell when the reach-the-exit rule is on.
+    exit: Option<(usize, usize)>,
+    /// Game mode of the current level.
+    mode: GameMode,
+    /// Mode of playlist levels that do not set their own.
+    default_mode: GameMode,
+    movement: MovementMode,
+    /// Seed of the latest generated maze; handcrafted maps leave it alone.
+    seed: u64,
+    /// Whether a round has been played on `seed`, so the next generated level needs a new one.
+    seed_played: bool,
+    /// What `Map` announces to rebuild the current maze: its seed and the percent of dead
+    /// ends opened, `None` and `0` for a handcrafted map.
+    map_seed: Option<u64>,
+    dead_ends: usize,
+    /// Whether seeds follow from a configured one rather than being random.
+    seeded: bool,
+    /// Algorithm of the current maze, and the ones levels take turns with; never empty.
+    algorithm: Algorithm,
+    algorithms: Vec<Algorithm>,
+    /// Levels to play, replacing the difficulty and size votes.
+    playlist: Option<Playlist>,
+    /// Length of the current round.
+    time_limit: std::time::Duration,
     round_state: RoundState,
-    round_seconds: u32,
+    /// Rounds started so far; the running or last round's number.
+    round_number: u32,
+    /// Time left in the current round state.
+    round_remaining: std::time::Duration,
     difficulty_idx: usize,
 }
 
 struct Player {
     id: u64,
+    name: String,
+    /// Team in team modes, fixed from join until the player leaves or a level changes the mode.
+    team: Option<u8>,
     x: usize,
     y: usize,
+    facing: Facing,
+    /// Highest input `seq` processed so far, applied or not.
+    last_input: u32,
+    limiter: StepLimiter,
+    /// Accepted actions waiting for `move_players`, oldest first.
+    moves: VecDeque<Action>,
+    /// Exact position in continuous movement, `None` on the grid.
+    motion: Option<Motion>,
+    /// Latest `Steer` in continuous movement, applied on every tick.
+    steering: Steering,
+    /// When this player may fire again.
+    next_shot_at: Option<std::time::Instant>,
+    /// Hits left; `MAX_HEALTH` after every spawn.
+    health: u32,
+    kills: u32,
+    deaths: u32,
+    score: i32,
+    /// Set while dead: when the player comes back.
+    respawn_at: Option<std::time::Instant>,
+    /// Lobby state, cleared whenever a new lobby opens.
+    ready: bool,
+    difficulty_vote: Option<Difficulty>,
+    size_vote: Option<MapSize>,
+    /// Index of the playlist level voted for.
+    level_vote: Option<usize>,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum RoundState { Lobby, InRound, Intermission }
+impl Player {
+    fn new(id: u64, name: String, team: Option<u8>, (x, y): (usize, usize), facing: Facing) -> Self {
+        Self {
+            id,
+            name,
+            team,
+            x,
+            y,
+            facing,
+            last_input: 0,
+            limiter: StepLimiter::default(),
+            moves: VecDeque::new(),
+            motion: None,
+            steering: Steering::default(),
+            next_shot_at: None,
+            health: MAX_HEALTH,
+            kills: 0,
+            deaths: 0,
+            score: 0,
+            respawn_at: None,
+            ready: false,
+            difficulty_vote: None,
+            size_vote: None,
+            level_vote: None,
+        }
+    }
+
+    /// Dead players are left out of snapshots, traces and spawn occupancy.
+    fn is_alive(&self) -> bool {
+        self.respawn_at.is_none()
+    }
 
-fn init_world() -> WorldState {
-    let env_diff = std::env::var("MAZE_DIFFICULTY").unwrap_or_else(|_| "medium".to_string());
-    let (w, h) = (41usize, 31usize); // odd dims for nice grid
-    let mut cells = generate_maze(w, h);
-    apply_difficulty(&mut cells, w, h, &env_diff);
-    let (sx, sy) = find_spawn(&cells, w, h);
-    let idx = match env_diff.as_str() { "easy" => 0, "medium" => 1, _ => 2 };
-    WorldState { map_width: w, map_height: h, map_cells: cells, spawn_x: sx, spawn_y: sy, players: HashMap::new(), round_state: RoundState::Lobby, round_seconds: 5, difficulty_idx: idx }
-}
+    fn state(&self) -> PlayerState {
+        PlayerState { id: self.id, x: self.x, y: self.y, facing: self.facing, last_input: self.last_input, team: self.team, motion: self.motion() }
+    }
 
-fn broadcast_players(server: &mut RenetServer, world: &WorldState) {
-    let list: Vec<serde_json::Value> = world
-        .players
-        .values()
-        .map(|p| serde_json::json!({"id": p.id, "x": p.x, "y": p.y}))
-        .collect();
-    let msg = serde_json::json!({"event": "Players", "body": list});
-    if let Ok(bytes) = serde_json::to_vec(&msg) {
-        for id in server.clients_id() {
-            server.send_message(id, DefaultChannel::ReliableOrdered, bytes.clone());
-        }
+    /// Continuous position, recentred whenever spawning or a push has moved the player to
+    /// another cell.
+    fn motion(&self) -> Option<Motion> {
+        let m = self.motion?;
+        Some(if m.cell() == (self.x, self.y) { m } else { Motion::centered((self.x, self.y), self.facing) })
+    }
+
+    fn pose(&self) -> movement::Pose {
+        movement::Pose { x: self.x, y: self.y, facing: self.facing }
     }
 }
 
-fn broadcast_map(server: &mut RenetServer, world: &WorldState) {
-    let map_msg = serde_json::json!({
-        "event": "Map",
-        "body": {
-            "width": world.map_width,
-            "height": world.map_height,
-            "cells": world.map_cells,
-        }
-    });
-    if let Ok(bytes) = serde_json::to_vec(&map_msg) {
-        for id in server.clients_id() {
-            server.send_message(id, DefaultChannel::ReliableOrdered, bytes.clone());
-        }
+fn init_world(
+    rules: &RoundRules,
+    mode: GameMode,
+    movement: MovementMode,
+    seed: Option<u64>,
+    algorithms: Vec<Algorithm>,
+    playlist: Option<Playlist>,
+) -> WorldState {
+    let env_diff = std::env::var("MAZE_DIFFICULTY").ok().and_then(|d| Difficulty::parse(&d)).unwrap_or(Difficulty::Medium);
+    let (w, h) = MapSize::default().dimensions();
+    let seeded = seed.is_some();
+    let seed = seed.unwrap_or_else(rand::random);
+    let algorithm = algorithms[0];
+    let cells = maze::generate(seed, w, h, env_diff, algorithm);
+    let (sx, sy) = find_spawn(&cells, w, h);
+    let idx = Difficulty::ALL.iter().position(|d| *d == env_diff).unwrap_or(1);
+    let exit = rules.exit.then(|| farthest_cell(&cells, w, h, (sx, sy)));
+    WorldState {
+        map_width: w,
+        map_height: h,
+        map_cells: cells,
+        map_size: MapSize::default(),
+        spawn_x: sx,
+        spawn_y: sy,
+        spawn_points: Vec::new(),
+        players: HashMap::new(),
+        exit,
+        mode,
+        default_mode: mode,
+        movement,
+        seed,
+        seed_played: false,
+        map_seed: Some(seed),
+        dead_ends: maze::dead_end_percent(env_diff),
+        seeded,
+        algorithm,
+        algorithms,
+        playlist,
+        time_limit: rules.time_limit,
+        round_state: RoundState::Lobby,
+        round_number: 0,
+        round_remaining: rules.lobby_timeout,
+        difficulty_idx: idx,
     }
 }
 
-fn find_spawn(cells: &Vec<u8>, w: usize, h: usize) -> (usize, usize) {
+fn map_message(world: &WorldState) -> ServerMessage {
+    ServerMessage::Map { width: world.map_width, height: world.map_height, cells: world.map_cells.clone(), exit: world.exit, seed: world.map_seed, dead_ends: world.dead_ends }
+}
+
+fn broadcast_map(server: &mut RenetServer, connections: &Connections, world: &WorldState) {
+    broadcast_message(server, connections, &map_message(world));
+}
+
+fn find_spawn(cells: &[u8], w: usize, h: usize) -> (usize, usize) {
     // pick first passage near center
     let cx = w / 2; let cy = h / 2;
     for r in 0..(w.max(h)) {
@@ -268,18 +1239,43 @@ fn find_spawn(cells: &Vec<u8>, w: usize, h: usize) -> (usize, usize) {
     (1,1)
 }
 
-fn cell_is_open(cells: &Vec<u8>, w: usize, x: usize, y: usize) -> bool {
+/// Open cell with the longest walk from `from`, found by breadth-first search; used as the exit.
+fn farthest_cell(cells: &[u8], w: usize, h: usize, from: (usize, usize)) -> (usize, usize) {
+    let mut seen = vec![false; w * h];
+    let mut queue = VecDeque::from([from]);
+    seen[from.1 * w + from.0] = true;
+    let mut last = from;
+    while let Some((x, y)) = queue.pop_front() {
+        last = (x, y);
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            if nx < 0 || ny < 0 || nx as usize >= w || ny as usize >= h { continue; }
+            let (nx, ny) = (nx as usize, ny as usize);
+            if cell_is_open(cells, w, nx, ny) && !seen[ny * w + nx] {
+                seen[ny * w + nx] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    last
+}
+
+fn cell_is_open(cells: &[u8], w: usize, x: usize, y: usize) -> bool {
     cells[y * w + x] == 0
 }
 
 fn is_occupied(players: &HashMap<u64, Player>, x: usize, y: usize) -> bool {
-    players.values().any(|p| p.x == x && p.y == y)
+    players.values().any(|p| p.is_alive() && p.x == x && p.y == y)
 }
 
-// Find a spawn near the center that is open and not currently used by any player
-fn find_unused_spawn(world: &WorldState) -> (usize, usize) {
+/// Free spawn marker nearest to `(cx, cy)` on maps that have markers, otherwise the open,
+/// unoccupied cell nearest to it.
+fn find_unused_spawn(world: &WorldState, (cx, cy): (usize, usize)) -> (usize, usize) {
+    let free_marker = world.spawn_points.iter().filter(|&&(x, y)| !is_occupied(&world.players, x, y)).min_by_key(|&&(x, y)| x.abs_diff(cx) + y.abs_diff(cy));
+    if let Some(&marker) = free_marker {
+        return marker;
+    }
     let w = world.map_width; let h = world.map_height;
-    let cx = w / 2; let cy = h / 2;
     for r in 0..(w.max(h)) {
         for dy in -(r as isize)..=(r as isize) {
             for dx in -(r as isize)..=(r as isize) {
@@ -296,12 +1292,73 @@ fn find_unused_spawn(world: &WorldState) -> (usize, usize) {
     (world.spawn_x, world.spawn_y)
 }
 
+/// Open, unoccupied cell, or spawn marker, farthest from the nearest living enemy, so a
+/// respawn does not land in someone's sights. Falls back to `find_unused_spawn` when nobody
+/// else is alive.
+fn find_spawn_away_from_enemies(world: &WorldState, id: u64) -> (usize, usize) {
+    let enemies: Vec<(usize, usize)> = world.players.values().filter(|p| p.id != id && p.is_alive()).map(|p| (p.x, p.y)).collect();
+    if enemies.is_empty() {
+        return find_unused_spawn(world, (world.map_width / 2, world.map_height / 2));
+    }
+    let w = world.map_width;
+    let candidates: Vec<(usize, usize)> = match world.spawn_points.is_empty() {
+        true => (0..world.map_cells.len()).map(|i| (i % w, i / w)).collect(),
+        false => world.spawn_points.clone(),
+    };
+    candidates
+        .into_iter()
+        .filter(|&(x, y)| cell_is_open(&world.map_cells, w, x, y) && !is_occupied(&world.players, x, y))
+        .max_by_key(|&(x, y)| enemies.iter().map(|&(ex, ey)| x.abs_diff(ex) + y.abs_diff(ey)).min())
+        .unwrap_or((world.spawn_x, world.spawn_y))
+}
+
+/// Where a player's spawn search starts. Teams sit evenly around the edge of the maze, so
+/// two teams start on opposite sides; free-for-all spawns from the centre.
+fn team_anchor(team: Option<u8>, mode: GameMode, w: usize, h: usize) -> (usize, usize) {
+    let (Some(team), Some(teams)) = (team, mode.teams()) else { return (w / 2, h / 2) };
+    let angle = std::f32::consts::TAU * team as f32 / teams as f32;
+    let x = w as f32 / 2.0 + angle.cos() * (w as f32 / 2.0 - 2.0);
+    let y = h as f32 / 2.0 + angle.sin() * (h as f32 / 2.0 - 2.0);
+    (x.round() as usize, y.round() as usize)
+}
+
+fn spawn_anchor(world: &WorldState, team: Option<u8>) -> (usize, usize) {
+    team_anchor(team, world.mode, world.map_width, world.map_height)
+}
+
+/// Team with the fewest players, lowest id on a tie; `None` in free-for-all.
+fn balanced_team(world: &WorldState) -> Option<u8> {
+    let teams = world.mode.teams()?;
+    (0..teams).min_by_key(|team| world.players.values().filter(|p| p.team == Some(*team)).count())
+}
+
+/// Deals everyone into `mode`'s teams afresh, by client id, when a level changes the mode.
+fn assign_teams(players: &mut HashMap<u64, Player>, mode: GameMode) {
+    let mut ids: Vec<u64> = players.keys().copied().collect();
+    ids.sort_unstable();
+    for (n, id) in ids.into_iter().enumerate() {
+        if let Some(p) = players.get_mut(&id) {
+            p.team = mode.teams().map(|teams| (n % teams as usize) as u8);
+        }
+    }
+}
+
 // Assign unique spawns to all players for a fresh maze
-fn allocate_unique_spawns_for_all(players: &mut HashMap<u64, Player>, cells: &Vec<u8>, w: usize, h: usize) {
+fn allocate_unique_spawns_for_all(
+    players: &mut HashMap<u64, Player>,
+    cells: &[u8],
+    w: usize,
+    h: usize,
+    markers: &[(usize, usize)],
+    mode: GameMode,
+) {
     let mut used: HashSet<(usize, usize)> = HashSet::new();
-    let cx = w / 2; let cy = h / 2;
-    // helper to get next available open cell not in `used`
-    let next_open = |used: &HashSet<(usize, usize)>| -> (usize, usize) {
+    // helper to get the unused marker, or else the next available open cell, near `(cx, cy)`
+    let next_open = |used: &HashSet<(usize, usize)>, (cx, cy): (usize, usize)| -> (usize, usize) {
+        let marker = markers.iter().filter(|m| !used.contains(*m)).min_by_key(|&&(x, y)| x.abs_diff(cx) + y.abs_diff(cy));
+        if let Some(&marker) = marker {
+            return marker;
+        }
         for r in 0..(w.max(h)) {
             for dy in -(r as isize)..=(r as isize) {
                 for dx in -(r as isize)..=(r as isize) {
@@ -323,159 +1380,372 @@ fn allocate_unique_spawns_for_all(players: &mut HashMap<u64, Player>, cells: &Ve
     ids.sort_unstable();
     for id in ids {
         if let Some(p) = players.get_mut(&id) {
-            let pos = next_open(&used);
+            let pos = next_open(&used, team_anchor(p.team, mode, w, h));
             p.x = pos.0; p.y = pos.1;
+            p.facing = movement::open_facing(cells, w, h, pos);
             used.insert(pos);
         }
     }
 }
 
-fn generate_maze(w: usize, h: usize) -> Vec<u8> {
-    // Perfect maze via recursive backtracker on cell grid (odd-sized grid)
-    assert!(w % 2 == 1 && h % 2 == 1);
-    let cw = (w - 1) / 2; // cells in x
-    let ch = (h - 1) / 2; // cells in y
-    let mut grid = vec![1u8; w * h]; // 1=wall, 0=passage
-    let mut visited = vec![false; cw * ch];
-    let mut stack: Vec<(usize, usize)> = Vec::new();
-    let mut rng = rand::rng();
+/// Pause between a round's end and the next lobby. The lobby and the round themselves
+/// last as long as `RoundRules` says.
+const INTERMISSION_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+/// Round results kept for `QueryHistory`.
+const ROUND_HISTORY: usize = 20;
 
-    push_cell_fn(&mut grid, &mut visited, w, cw, 0, 0, &mut stack);
-    while let Some((cx, cy)) = stack.pop() {
-        // collect neighbors
-        let mut neighbors = Vec::new();
-        if cx > 0 && !visited[cy * cw + (cx - 1)] { neighbors.push((cx - 1, cy, (2*cy+1, 2*cx))); }
-        if cy > 0 && !visited[(cy - 1) * cw + cx] { neighbors.push((cx, cy - 1, (2*cy, 2*cx+1))); }
-        if cx + 1 < cw && !visited[cy * cw + (cx + 1)] { neighbors.push((cx + 1, cy, (2*cy+1, 2*cx+2))); }
-        if cy + 1 < ch && !visited[(cy + 1) * cw + cx] { neighbors.push((cx, cy + 1, (2*cy+2, 2*cx+1))); }
+/// Active end conditions.
+#[derive(Resource)]
+struct Rules(RoundRules);
 
-        if !neighbors.is_empty() {
-            // push current back to continue later
-            stack.push((cx, cy));
-            let i: usize = rng.random_range(0..neighbors.len());
-            let (nx, ny, (wy, wx)) = neighbors[i];
-            // knock down wall between (cx,cy) and (nx,ny)
-            grid[wy * w + wx] = 0;
-            push_cell_fn(&mut grid, &mut visited, w, cw, nx, ny, &mut stack);
-        }
+fn maze_algorithms(configured: &[Algorithm]) -> Vec<Algorithm> {
+    let configured = if configured.is_empty() { vec![Algorithm::default()] } else { configured.to_vec() };
+    match std::env::var("MAZE_ALGORITHMS") {
+        Ok(v) if v == "all" => Algorithm::ALL.to_vec(),
+        Ok(v) => v.split(',').map(|name| Algorithm::parse(name.trim())).collect::<Option<Vec<_>>>().filter(|a| !a.is_empty()).unwrap_or_else(|| {
+            let names: Vec<&str> = Algorithm::ALL.iter().map(|a| a.as_str()).collect();
+            eprintln!("ignoring MAZE_ALGORITHMS={v}: expected all or a list of {}", names.join(", "));
+            configured
+        }),
+        Err(_) => configured,
     }
+}
 
-    grid
+fn playlist(configured: Option<Playlist>) -> Option<Playlist> {
+    match std::env::var("MAZE_PLAYLIST") {
+        Ok(v) => Playlist::load(std::path::Path::new(&v)).map(Some).unwrap_or_else(|e| {
+            eprintln!("ignoring MAZE_PLAYLIST={v}: {e}");
+            configured
+        }),
+        Err(_) => configured,
+    }
 }
 
-fn push_cell_fn(
-    grid: &mut [u8],
-    visited: &mut [bool],
-    w: usize,
-    cw: usize,
-    cx: usize,
-    cy: usize,
-    stack: &mut Vec<(usize, usize)>,
-) {
-    visited[cy * cw + cx] = true;
-    grid[(2 * cy + 1) * w + (2 * cx + 1)] = 0;
-    stack.push((cx, cy));
-}
-
-fn reduce_dead_ends(cells: &mut Vec<u8>, w: usize, h: usize, ratio: f32) {
-    // Connect some dead ends to create loops, reducing dead-end count.
-    let mut rng = rand::rng();
-    let mut dead_ends: Vec<(usize, usize)> = Vec::new();
-    for y in 1..(h - 1) {
-        for x in 1..(w - 1) {
-            if cells[y * w + x] == 0 {
-                let mut walls = 0;
-                if cells[(y - 1) * w + x] == 1 { walls += 1; }
-                if cells[(y + 1) * w + x] == 1 { walls += 1; }
-                if cells[y * w + (x - 1)] == 1 { walls += 1; }
-                if cells[y * w + (x + 1)] == 1 { walls += 1; }
-                if walls == 3 { dead_ends.push((x, y)); }
-            }
-        }
+fn maze_seed(configured: Option<u64>) -> Option<u64> {
+    match std::env::var("MAZE_SEED") {
+        Ok(v) => v.parse().map(Some).unwrap_or_else(|_| {
+            eprintln!("ignoring MAZE_SEED={v}: expected an unsigned 64-bit number");
+            configured
+        }),
+        Err(_) => configured,
     }
-    let target = ((dead_ends.len() as f32) * ratio) as usize;
-    for _ in 0..target {
-        if dead_ends.is_empty() { break; }
-    let idx: usize = rng.random_range(0..dead_ends.len());
-        let (x, y) = dead_ends.remove(idx);
-        // carve a random adjacent wall to open a loop
-        let mut candidates = Vec::new();
-        if cells[(y - 1) * w + x] == 1 { candidates.push((x, y - 1)); }
-        if cells[(y + 1) * w + x] == 1 { candidates.push((x, y + 1)); }
-        if cells[y * w + (x - 1)] == 1 { candidates.push((x - 1, y)); }
-        if cells[y * w + (x + 1)] == 1 { candidates.push((x + 1, y)); }
-        if !candidates.is_empty() {
-            let j: usize = rng.random_range(0..candidates.len());
-            let (wx, wy) = candidates[j];
-            cells[wy * w + wx] = 0;
-        }
+}
+
+fn round_rules(configured: RoundRules) -> RoundRules {
+    match std::env::var("MAZE_RULES") {
+        Ok(v) => RoundRules::parse(&v).unwrap_or_else(|| {
+            eprintln!("ignoring MAZE_RULES={v}: expected a list like score:500,last,exit,time:120");
+            configured
+        }),
+        Err(_) => configured,
     }
 }
 
-fn apply_difficulty(cells: &mut Vec<u8>, w: usize, h: usize, difficulty: &str) {
-    match difficulty {
-        "easy" => reduce_dead_ends(cells, w, h, 0.5),
-        "medium" => reduce_dead_ends(cells, w, h, 0.2),
-        _ => {} // hard = perfect
+fn game_mode(configured: GameMode) -> GameMode {
+    match std::env::var("MAZE_MODE") {
+        Ok(v) => GameMode::parse(&v).unwrap_or_else(|| {
+            eprintln!("ignoring MAZE_MODE={v}: expected ffa, teams or teams:<count>, optionally with :ff");
+            configured
+        }),
+        Err(_) => configured,
     }
 }
 
-fn tick_rounds(time: Res<bevy_time::Time>, mut server: ResMut<RenetServer>, mut world: ResMut<WorldState>) {
+/// Results of the last `ROUND_HISTORY` rounds, oldest first.
+#[derive(Resource, Default)]
+struct RoundHistory(VecDeque<RoundResult>);
+
+fn tick_rounds(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    connections: Res<Connections>,
+    mut world: ResMut<WorldState>,
+    rules: Res<Rules>,
+    mut history: ResMut<RoundHistory>,
+) {
     // Simple state machine:
-    // Lobby (5s) -> InRound (30s) -> Intermission (5s) -> next difficulty -> Lobby
-    let dt = time.delta_secs();
-    if dt <= 0.0 { return; }
-
-    // coarse countdown in whole seconds
-    let sub = (dt.ceil() as u32).max(1);
-    if world.round_seconds > 0 { world.round_seconds = world.round_seconds.saturating_sub(sub); }
-
-    if world.round_seconds == 0 {
-        match world.round_state {
-            RoundState::Lobby => {
-                world.round_state = RoundState::InRound;
-                world.round_seconds = 30;
-                broadcast_round(&mut server, &world);
+    // Lobby (until enough players are ready) -> level from votes -> InRound -> Intermission -> Lobby
+    // Runs on the fixed timestep, so `delta` is exactly one tick
+    world.round_remaining = world.round_remaining.saturating_sub(time.delta());
+
+    match world.round_state {
+        RoundState::Lobby => {
+            // An empty server idles in the lobby
+            if world.players.is_empty() {
+                world.round_remaining = rules.0.lobby_timeout;
+                return;
             }
-            RoundState::InRound => {
-                world.round_state = RoundState::Intermission;
-                world.round_seconds = 5;
-                broadcast_round(&mut server, &world);
+            let ready = world.players.values().filter(|p| p.ready).count();
+            if ready < rules.0.ready_needed(world.players.len()) && !world.round_remaining.is_zero() { return; }
+            next_level(&mut world, &rules.0);
+            broadcast_map(&mut server, &connections, &world);
+            world.round_state = RoundState::InRound;
+            world.round_remaining = world.time_limit;
+            world.round_number += 1;
+            // Scores count per round
+            for p in world.players.values_mut() {
+                (p.kills, p.deaths, p.score) = (0, 0, 0);
             }
-            RoundState::Intermission => {
-                // Cycle difficulty and regenerate maze
-                world.difficulty_idx = (world.difficulty_idx + 1) % 3;
-                let difficulty = match world.difficulty_idx { 0 => "easy", 1 => "medium", _ => "hard" };
-                let (w, h) = (world.map_width, world.map_height);
-                let mut cells = generate_maze(w, h);
-                apply_difficulty(&mut cells, w, h, difficulty);
-                world.map_cells = cells;
-                (world.spawn_x, world.spawn_y) = find_spawn(&world.map_cells, w, h);
-                // Allocate unique spawns for all players on the new maze
-                let cells_snapshot = world.map_cells.clone();
-                let mut players_tmp = std::mem::take(&mut world.players);
-                allocate_unique_spawns_for_all(&mut players_tmp, &cells_snapshot, w, h);
-                world.players = players_tmp;
-                broadcast_map(&mut server, &world);
-                broadcast_players(&mut server, &world);
-                world.round_state = RoundState::Lobby;
-                world.round_seconds = 5;
-                broadcast_round(&mut server, &world);
+            broadcast_round(&mut server, &connections, &world);
+            broadcast_scoreboard(&mut server, &connections, &world);
+        }
+        RoundState::InRound => {
+            let players: Vec<Contender> = world
+                .players
+                .values()
+                .map(|p| Contender { id: p.id, team: p.team, score: p.score, alive: p.is_alive(), cell: (p.x, p.y) })
+                .collect();
+            let time_up = world.round_remaining.is_zero();
+            let Some((reason, winner, team)) = rules::round_end(&rules.0, world.mode, &players, world.exit, time_up) else { return };
+            let result = RoundResult {
+                round: world.round_number,
+                winner,
+                team,
+                reason,
+                scores: standings(&world),
+                teams: team_standings(&world),
+                duration_ms: world.time_limit.saturating_sub(world.round_remaining).as_millis() as u32,
+                difficulty: difficulty_from_idx(world.difficulty_idx),
+            };
+            match (winner.and_then(|id| world.players.get(&id)), team) {
+                (Some(p), _) => println!("round {} won by {} ({reason:?})", result.round, p.name),
+                (None, Some(team)) => println!("round {} won by team {} ({reason:?})", result.round, team + 1),
+                (None, None) => println!("round {} ended in a draw ({reason:?})", result.round),
+            }
+            broadcast_message(&mut server, &connections, &ServerMessage::RoundResult(result.clone()));
+            if history.0.len() == ROUND_HISTORY {
+                history.0.pop_front();
+            }
+            history.0.push_back(result);
+
+            world.round_state = RoundState::Intermission;
+            world.round_remaining = INTERMISSION_DURATION;
+            broadcast_round(&mut server, &connections, &world);
+            // Final standings
+            broadcast_scoreboard(&mut server, &connections, &world);
+        }
+        RoundState::Intermission if world.round_remaining.is_zero() => {
+            world.round_state = RoundState::Lobby;
+            world.round_remaining = rules.0.lobby_timeout;
+            for p in world.players.values_mut() {
+                (p.ready, p.difficulty_vote, p.size_vote, p.level_vote) = (false, None, None, None);
             }
+            broadcast_round(&mut server, &connections, &world);
+            broadcast_lobby(&mut server, &connections, &world, &rules.0);
         }
+        _ => {}
     }
 }
 
-fn broadcast_round(server: &mut RenetServer, world: &WorldState) {
-    let difficulty = match world.difficulty_idx { 0 => "easy", 1 => "medium", _ => "hard" };
-    let state = match world.round_state { RoundState::Lobby => "Lobby", RoundState::InRound => "InRound", RoundState::Intermission => "Intermission" };
-    let msg = serde_json::json!({
-        "event": "Round",
-        "body": {"state": state, "difficulty": difficulty, "remaining": world.round_seconds}
+/// Seed for the next generated maze. The first one played is the configured seed itself;
+/// after that seeds follow from it, or are random without one.
+fn next_seed(world: &mut WorldState) -> u64 {
+    if world.seed_played {
+        world.seed = if world.seeded { maze::MazeRng::new(world.seed).next_u64() } else { rand::random() };
+    }
+    world.seed_played = true;
+    world.map_seed = Some(world.seed);
+    world.seed
+}
+
+/// Builds the level for the round about to start: the playlist's next level, or without a
+/// playlist one from the lobby's votes. Ties are broken at random. Without difficulty votes
+/// the difficulty cycles, except for the very first round, which keeps `MAZE_DIFFICULTY`;
+/// without size votes the size stays.
+fn next_level(world: &mut WorldState, rules: &RoundRules) {
+    let level_votes: Vec<usize> = world.players.values().filter_map(|p| p.level_vote).collect();
+    let level = world.playlist.as_mut().map(|playlist| {
+        let options: Vec<usize> = (0..playlist.levels.len()).collect();
+        let index = playlist.advance(vote_winner(&level_votes, &options));
+        (index, playlist.levels[index].clone())
     });
-    if let Ok(bytes) = serde_json::to_vec(&msg) {
-        for id in server.clients_id() {
-            server.send_message(id, DefaultChannel::ReliableOrdered, bytes.clone());
+    let (w, h, cells, mode) = match level {
+        Some((index, level)) => {
+            world.difficulty_idx = Difficulty::ALL.iter().position(|d| *d == level.difficulty).unwrap_or(0);
+            world.map_size = level.size;
+            world.algorithm = level.algorithm;
+            world.time_limit = level.time_limit.unwrap_or(rules.time_limit);
+            world.spawn_points = level.map.as_ref().map_or(Vec::new(), |map| map.spawns.clone());
+            let (w, h, cells) = match &level.map {
+                Some(map) => {
+                    world.map_seed = None;
+                    world.dead_ends = 0;
+                    (map.width, map.height, map.cells.clone())
+                }
+                None => {
+                    let (w, h) = level.size.dimensions();
+                    let seed = next_seed(world);
+                    world.dead_ends = level.dead_ends;
+                    (w, h, maze::generate_with(seed, w, h, level.dead_ends, level.algorithm))
+                }
+            };
+            match &level.map {
+                Some(map) => println!("next level {}: {}, map {} ({} votes)", index + 1, level.name, map.name, level_votes.len()),
+                None => println!(
+                    "next level {}: {}, {} {} maze with {}% dead ends opened, seed {} ({} votes)",
+                    index + 1, level.name, level.size.as_str(), level.algorithm.as_str(), level.dead_ends, world.seed, level_votes.len()
+                ),
+            }
+            (w, h, cells, level.mode.unwrap_or(world.default_mode))
+        }
+        None => {
+            let difficulty_votes: Vec<Difficulty> = world.players.values().filter_map(|p| p.difficulty_vote).collect();
+            let size_votes: Vec<MapSize> = world.players.values().filter_map(|p| p.size_vote).collect();
+            let difficulty = vote_winner(&difficulty_votes, &Difficulty::ALL).unwrap_or_else(|| match world.round_number {
+                0 => difficulty_from_idx(world.difficulty_idx),
+                _ => difficulty_from_idx(world.difficulty_idx + 1),
+            });
+            world.difficulty_idx = Difficulty::ALL.iter().position(|d| *d == difficulty).unwrap_or(0);
+            world.map_size = vote_winner(&size_votes, &MapSize::ALL).unwrap_or(world.map_size);
+            world.algorithm = world.algorithms[world.round_number as usize % world.algorithms.len()];
+            world.time_limit = rules.time_limit;
+            world.spawn_points = Vec::new();
+            world.dead_ends = maze::dead_end_percent(difficulty);
+            let (w, h) = world.map_size.dimensions();
+            let seed = next_seed(world);
+            println!(
+                "next level: {} {} {} maze, seed {} ({} difficulty votes, {} size votes)",
+                difficulty.as_str(), world.map_size.as_str(), world.algorithm.as_str(), seed, difficulty_votes.len(), size_votes.len()
+            );
+            (w, h, maze::generate(seed, w, h, difficulty, world.algorithm), world.mode)
+        }
+    };
+    (world.map_width, world.map_height, world.map_cells) = (w, h, cells);
+    (world.spawn_x, world.spawn_y) = world.spawn_points.first().copied().unwrap_or_else(|| find_spawn(&world.map_cells, w, h));
+    world.exit = rules.exit.then(|| farthest_cell(&world.map_cells, w, h, (world.spawn_x, world.spawn_y)));
+    if mode != world.mode {
+        println!("game mode changes to {mode:?}");
+        world.mode = mode;
+        assign_teams(&mut world.players, mode);
+    }
+    // Everyone starts the round alive, including players still waiting to respawn
+    let WorldState { players, map_cells, spawn_points, mode, .. } = world;
+    for p in players.values_mut() {
+        p.health = MAX_HEALTH;
+        p.respawn_at = None;
+    }
+    allocate_unique_spawns_for_all(players, map_cells, w, h, spawn_points, *mode);
+    let spawns: Vec<(usize, usize)> = players.values().map(|p| (p.x, p.y)).collect();
+    println!("level stats: {}", MazeStats::analyse(map_cells, w, h, &spawns));
+}
+
+// ---------------- Lobby -----------------
+
+fn lobby_status(world: &WorldState, rules: &RoundRules) -> ServerMessage {
+    let mut ready: Vec<u64> = world.players.values().filter(|p| p.ready).map(|p| p.id).collect();
+    ready.sort_unstable();
+    let difficulty_votes: Vec<Difficulty> = world.players.values().filter_map(|p| p.difficulty_vote).collect();
+    let size_votes: Vec<MapSize> = world.players.values().filter_map(|p| p.size_vote).collect();
+    let level_votes: Vec<usize> = world.players.values().filter_map(|p| p.level_vote).collect();
+    let levels: Vec<usize> = match &world.playlist {
+        Some(playlist) if playlist.order == playlist::Order::Vote => (0..playlist.levels.len()).collect(),
+        _ => Vec::new(),
+    };
+    ServerMessage::LobbyStatus {
+        ready,
+        needed: rules.ready_needed(world.players.len()) as u32,
+        difficulty_votes: tally(&difficulty_votes, &Difficulty::ALL),
+        size_votes: tally(&size_votes, &MapSize::ALL),
+        level_votes: tally(&level_votes, &levels).into_iter().map(|(i, n)| (i as u32 + 1, n)).collect(),
+    }
+}
+
+/// Sends the lobby's current state to everyone; a no-op outside the lobby.
+fn broadcast_lobby(server: &mut RenetServer, connections: &Connections, world: &WorldState, rules: &RoundRules) {
+    if world.round_state == RoundState::Lobby {
+        broadcast_message(server, connections, &lobby_status(world, rules));
+    }
+}
+
+fn round_message(world: &WorldState) -> ServerMessage {
+    ServerMessage::Round {
+        state: world.round_state,
+        difficulty: difficulty_from_idx(world.difficulty_idx),
+        algorithm: world.algorithm,
+        level: world.playlist.as_ref().and_then(|l| l.current()).map(|(i, level)| LevelInfo { number: i as u32 + 1, name: level.name.clone() }),
+        remaining_ms: world.round_remaining.as_millis() as u32,
+    }
+}
+
+fn broadcast_round(server: &mut RenetServer, connections: &Connections, world: &WorldState) {
+    broadcast_message(server, connections, &round_message(world));
+}
+
+fn difficulty_from_idx(idx: usize) -> Difficulty {
+    Difficulty::ALL[idx % Difficulty::ALL.len()]
+}
+
+// ---------------- Snapshots -----------------
+
+/// Snapshot clock, driven by the fixed timestep.
+#[derive(Resource)]
+struct Snapshots {
+    timer: bevy_time::Timer,
+    /// Tick of the newest snapshot sent; acks beyond it are bogus.
+    last_tick: u64,
+}
+
+impl Snapshots {
+    fn new(hz: f32) -> Self {
+        let timer = bevy_time::Timer::from_seconds(1.0 / hz.max(1.0), bevy_time::TimerMode::Repeating);
+        Self { timer, last_tick: 0 }
+    }
+}
+
+/// Visibility rule applied to every snapshot.
+#[derive(Resource)]
+struct Interest(Visibility);
+
+fn interest_rule(configured: Visibility) -> Visibility {
+    match std::env::var("MAZE_VISIBILITY") {
+        Ok(v) => Visibility::parse(&v).unwrap_or_else(|| {
+            eprintln!("ignoring MAZE_VISIBILITY={v}: expected full, los or los:<radius>");
+            configured
+        }),
+        Err(_) => configured,
+    }
+}
+
+fn send_snapshots(
+    time: Res<Time>,
+    mut snapshots: ResMut<Snapshots>,
+    mut server: ResMut<RenetServer>,
+    mut connections: ResMut<Connections>,
+    world: Res<WorldState>,
+    interest: Res<Interest>,
+) {
+    snapshots.timer.tick(time.delta());
+    if !snapshots.timer.just_finished() { return; }
+    let tick = connections.tick;
+    snapshots.last_tick = tick;
+    let table: PlayerTable = world.players.values().filter(|p| p.is_alive()).map(|p| (p.id, p.state())).collect();
+
+    // With full visibility everyone gets the same table, so clients acking the same tick with
+    // the same codec share one encoded delta
+    let shared = interest.0 == Visibility::Full;
+    let mut encoded: HashMap<(Option<u64>, Codec), Vec<u8>> = HashMap::new();
+    for id in server.clients_id() {
+        // Only players that finished the Hello flow get snapshots
+        if !world.players.contains_key(&id) { continue; }
+        let Some(conn) = connections.clients.get_mut(&id) else { continue };
+        let visible = interest.0.filter(&table, id, &world.map_cells, world.map_width);
+        let baseline = conn.acked_tick.and_then(|t| conn.sent.iter().find(|(sent, _)| *sent == t));
+        let encode = || {
+            let snapshot = match baseline {
+                Some((base_tick, base)) => Snapshot::delta(tick, &visible, *base_tick, base),
+                None => Snapshot::full(tick, &visible),
+            };
+            ServerFrame { tick, message: ServerMessage::Snapshot(snapshot) }.to_bytes(conn.codec)
+        };
+        let bytes = match shared {
+            true => encoded.entry((baseline.map(|(t, _)| *t), conn.codec)).or_insert_with(encode).clone(),
+            false => encode(),
+        };
+        server.send_message(id, DefaultChannel::Unreliable, bytes);
+
+        if conn.sent.len() == SNAPSHOT_HISTORY {
+            conn.sent.pop_front();
         }
+        conn.sent.push_back((tick, visible));
     }
 }
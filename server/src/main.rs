@@ -1,7 +1,163 @@
-use server::run_server;
+use std::path::Path;
+
+use protocol::analysis::MazeStats;
+use protocol::maze::{self, Algorithm};
+use protocol::{Difficulty, MapSize};
+use server::mapfile::MapFile;
+use server::playlist::Playlist;
+use server::{auth, run, run_server, MazeWarsServerPlugin};
+
+const USAGE: &str = "\
+usage:
+  server [--bind ADDR] [--tick-hz N] [--seed N] [--playlist FILE]
+                                               unsecure server, UDP 0.0.0.0:5000 by default
+  server --key FILE [--bind ADDR] [--issuer ADDR] [--tick-hz N] [--seed N] [--playlist FILE]
+                                               secure server; --issuer serves tokens over TCP
+                                               --tick-hz sets the gameplay tick rate (30)
+                                               --seed replays the same sequence of mazes
+                                               --playlist plays the levels listed in FILE
+  server keygen FILE                           write a new private key
+  server map --seed N [--size S] [--difficulty D] [--algorithm A] [--out FILE]
+                                               write a generated maze as a map file
+  server checkmap FILE                         validate a map file and print its stats
+  server token --key FILE --user NAME [--bind ADDR] [--out FILE]
+                                               issue a connect token file for one player";
 
 fn main() {
-    // Start the MazeWars server on UDP 0.0.0.0:5000 with room for 32 clients.
-    run_server(None, Some(32));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = dispatch(&args) {
+        eprintln!("{e}\n\n{USAGE}");
+        std::process::exit(2);
+    }
+}
+
+fn dispatch(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        // Start the MazeWars server on UDP 0.0.0.0:5000 with room for 32 clients.
+        None => run_server(None, Some(32)),
+        Some("keygen") => {
+            let path = args.get(1).ok_or("keygen needs a file to write")?;
+            auth::write_key_file(Path::new(path), &auth::generate_key()).map_err(|e| format!("cannot write {path}: {e}"))?;
+            println!("wrote private key to {path}; keep it secret");
+        }
+        Some("map") => {
+            let opts = Options::parse(&args[1..])?;
+            let seed = opts.seed.ok_or("map needs --seed")?;
+            let seed: u64 = seed.parse().map_err(|_| format!("invalid seed {seed}"))?;
+            let size = match opts.size.as_deref() {
+                Some(size) => MapSize::ALL.into_iter().find(|s| s.as_str() == size).ok_or(format!("invalid size {size}"))?,
+                None => MapSize::default(),
+            };
+            let difficulty = match opts.difficulty.as_deref() {
+                Some(difficulty) => Difficulty::parse(difficulty).ok_or(format!("invalid difficulty {difficulty}"))?,
+                None => Difficulty::Medium,
+            };
+            let algorithm = match opts.algorithm.as_deref() {
+                Some(algorithm) => Algorithm::parse(algorithm).ok_or(format!("invalid algorithm {algorithm}"))?,
+                None => Algorithm::default(),
+            };
+            let (w, h) = size.dimensions();
+            let name = format!("{} {} {} maze, seed {seed}", difficulty.as_str(), size.as_str(), algorithm.as_str());
+            let map = MapFile::from_cells(name, w, h, maze::generate(seed, w, h, difficulty, algorithm));
+            match opts.out {
+                Some(out) => {
+                    std::fs::write(&out, map.to_string()).map_err(|e| format!("cannot write {out}: {e}"))?;
+                    println!("wrote {} to {out}", map.name);
+                }
+                None => print!("{map}"),
+            }
+        }
+        Some("checkmap") => {
+            let path = args.get(1).ok_or("checkmap needs a map file")?;
+            let map = MapFile::load(Path::new(path))?;
+            println!("{}: {}x{}, {} spawns", map.name, map.width, map.height, map.spawns.len());
+            println!("{}", MazeStats::analyse(&map.cells, map.width, map.height, &map.spawns));
+        }
+        Some("token") => {
+            let opts = Options::parse(&args[1..])?;
+            let key = auth::read_key_file(Path::new(opts.key.as_deref().ok_or("token needs --key")?))?;
+            let user = opts.user.ok_or("token needs --user")?;
+            let bind = opts.bind.parse().map_err(|_| format!("invalid bind address {}", opts.bind))?;
+            let token = auth::issue_token(&key, server::public_addresses(bind), &user)?;
+            let out = opts.out.unwrap_or_else(|| format!("{user}.token"));
+            let mut file = std::fs::File::create(&out).map_err(|e| format!("cannot create {out}: {e}"))?;
+            token.write(&mut file).map_err(|e| format!("cannot write {out}: {e}"))?;
+            println!("wrote token for {user} to {out}");
+        }
+        Some(_) => {
+            let opts = Options::parse(args)?;
+            let private_key = opts.key.as_deref().map(|key| auth::read_key_file(Path::new(key))).transpose()?;
+            if opts.issuer.is_some() && private_key.is_none() {
+                return Err("--issuer needs --key".into());
+            }
+            let defaults = MazeWarsServerPlugin::default();
+            let tick_hz = match opts.tick_hz {
+                Some(hz) => hz.parse().ok().filter(|hz| *hz > 0).ok_or(format!("invalid tick rate {hz}"))?,
+                None => defaults.tick_hz,
+            };
+            let maze_seed = opts.seed.map(|seed| seed.parse().map_err(|_| format!("invalid seed {seed}"))).transpose()?;
+            let playlist = opts.playlist.as_deref().map(|path| Playlist::load(Path::new(path))).transpose()?;
+            run(MazeWarsServerPlugin {
+                bind_addr: opts.bind,
+                tick_hz,
+                maze_seed,
+                playlist,
+                private_key,
+                token_issuer: opts.issuer,
+                ..defaults
+            });
+        }
+    }
+    Ok(())
 }
 
+struct Options {
+    key: Option<String>,
+    user: Option<String>,
+    bind: String,
+    issuer: Option<String>,
+    out: Option<String>,
+    tick_hz: Option<String>,
+    seed: Option<String>,
+    playlist: Option<String>,
+    size: Option<String>,
+    difficulty: Option<String>,
+    algorithm: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut opts = Options {
+            key: None,
+            user: None,
+            bind: "0.0.0.0:5000".into(),
+            issuer: None,
+            out: None,
+            tick_hz: None,
+            seed: None,
+            playlist: None,
+            size: None,
+            difficulty: None,
+            algorithm: None,
+        };
+        let mut iter = args.iter();
+        while let Some(flag) = iter.next() {
+            let value = iter.next().ok_or(format!("{flag} needs a value"))?.clone();
+            match flag.as_str() {
+                "--key" => opts.key = Some(value),
+                "--user" => opts.user = Some(value),
+                "--bind" => opts.bind = value,
+                "--issuer" => opts.issuer = Some(value),
+                "--out" => opts.out = Some(value),
+                "--tick-hz" => opts.tick_hz = Some(value),
+                "--seed" => opts.seed = Some(value),
+                "--playlist" => opts.playlist = Some(value),
+                "--size" => opts.size = Some(value),
+                "--difficulty" => opts.difficulty = Some(value),
+                "--algorithm" => opts.algorithm = Some(value),
+                _ => return Err(format!("unknown option {flag}")),
+            }
+        }
+        Ok(opts)
+    }
+}
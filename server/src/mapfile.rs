@@ -0,0 +1,141 @@
+//! Handcrafted maps: a text file with a short header and the maze drawn in ASCII.
+//!
+//! ```text
+//! name: Crossroads
+//! author: sam
+//! #########
+//! #S.....S#
+//! #.##.##.#
+//! #...#...#
+//! #########
+//! ```
+//!
+//! `#` is a wall, `.` a passage and `S` a passage players spawn on. The header takes
+//! `name` (required) and `author`. A map must be a rectangle of at least 3x3 with walls all
+//! around, and every passage must be reachable from every other.
+
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapFile {
+    pub name: String,
+    pub author: Option<String>,
+    pub width: usize,
+    pub height: usize,
+    /// Row-major, `0` passage / `1` wall, as in `WorldState::map_cells`.
+    pub cells: Vec<u8>,
+    /// `S` cells, row by row.
+    pub spawns: Vec<(usize, usize)>,
+}
+
+impl MapFile {
+    /// A generated maze, ready to be written out with `to_string`. It has no spawn markers,
+    /// so players spawn wherever the server would put them.
+    pub fn from_cells(name: impl Into<String>, width: usize, height: usize, cells: Vec<u8>) -> Self {
+        Self { name: name.into(), author: None, width, height, cells, spawns: Vec::new() }
+    }
+
+    /// Reads and validates a map file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Parses and validates map text.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().map(|(n, l)| (n + 1, l.trim_end())).peekable();
+        let (mut name, mut author) = (None, None);
+        while let Some((n, line)) = lines.next_if(|(_, l)| !l.starts_with('#')) {
+            if line.is_empty() { continue; }
+            match line.split_once(':').map(|(k, v)| (k.trim(), v.trim())) {
+                Some(("name", value)) if !value.is_empty() => name = Some(value.to_string()),
+                Some(("author", value)) if !value.is_empty() => author = Some(value.to_string()),
+                _ => return Err(format!("line {n}: expected 'name: ...' or 'author: ...' before the map, found '{line}'")),
+            }
+        }
+        let name = name.ok_or("the header needs a 'name: ...' line")?;
+
+        let rows: Vec<(usize, &str)> = lines.collect();
+        let rows = &rows[..rows.iter().rposition(|(_, l)| !l.is_empty()).map_or(0, |last| last + 1)];
+        let width = rows.first().map_or(0, |(_, r)| r.chars().count());
+        if rows.len() < 3 || width < 3 {
+            return Err("the map needs at least 3 rows of 3 cells".into());
+        }
+        let mut map = MapFile { name, author, width, height: rows.len(), cells: Vec::with_capacity(width * rows.len()), spawns: Vec::new() };
+        for (y, (n, row)) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(format!("line {n}: row is {} cells wide, the first is {width}", row.chars().count()));
+            }
+            for (x, c) in row.chars().enumerate() {
+                map.cells.push(match c {
+                    '#' => 1,
+                    '.' => 0,
+                    'S' => {
+                        map.spawns.push((x, y));
+                        0
+                    }
+                    _ => return Err(format!("line {n}: unexpected '{c}'; use # for walls, . for passages and S for spawns")),
+                });
+            }
+        }
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// Checks that the border is closed, spawns are on passages and every passage connects to
+    /// every other.
+    pub fn validate(&self) -> Result<(), String> {
+        let (w, h) = (self.width, self.height);
+        if w < 3 || h < 3 || self.cells.len() != w * h {
+            return Err(format!("a {w}x{h} map needs {} cells, not {}", w * h, self.cells.len()));
+        }
+        let open = |x: usize, y: usize| self.cells[y * w + x] == 0;
+        if let Some((x, y)) = (0..w * h).map(|i| (i % w, i / w)).find(|&(x, y)| (x == 0 || y == 0 || x == w - 1 || y == h - 1) && open(x, y)) {
+            return Err(format!("the border must be all walls, but ({x}, {y}) is open"));
+        }
+        if let Some((x, y)) = self.spawns.iter().find(|&&(x, y)| x >= w || y >= h || !open(x, y)) {
+            return Err(format!("spawn ({x}, {y}) is not on a passage"));
+        }
+        let Some(start) = (0..w * h).find(|i| self.cells[*i] == 0) else {
+            return Err("the map has no passages".into());
+        };
+        let mut seen = vec![false; w * h];
+        seen[start] = true;
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            // The closed border keeps the neighbours of a passage inside the grid
+            for n in [i - 1, i + 1, i - w, i + w] {
+                if self.cells[n] == 0 && !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+        match (0..w * h).find(|i| self.cells[*i] == 0 && !seen[*i]) {
+            Some(i) => Err(format!("({}, {}) cannot be reached from ({}, {})", i % w, i / w, start % w, start / w)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes the map in the format `parse` reads.
+impl fmt::Display for MapFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        if let Some(author) = &self.author {
+            writeln!(f, "author: {author}")?;
+        }
+        for y in 0..self.height {
+            let row: String = (0..self.width)
+                .map(|x| match self.cells[y * self.width + x] {
+                    0 if self.spawns.contains(&(x, y)) => 'S',
+                    0 => '.',
+                    _ => '#',
+                })
+                .collect();
+            writeln!(f, "{row}")?;
+        }
+        Ok(())
+    }
+}
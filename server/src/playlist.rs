@@ -0,0 +1,171 @@
+//! Level playlists: a file listing the levels a server plays, and the order it moves through
+//! them. Without one, levels come from the lobby's difficulty and size votes.
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! order shuffle
+//! level Warm-up = size:small,algorithm:prim,dead_ends:50,time:60
+//! level Team arena = size:large,algorithm:rooms,mode:teams:2,time:120
+//! level Crossroads = map:maps/crossroads.txt,mode:ffa
+//! ```
+//!
+//! `order` is `loop` (the default), `shuffle` or `vote`. Level settings are all optional:
+//! `size`, `algorithm`, `difficulty` (which also sets `dead_ends`), `dead_ends` (percent of
+//! dead ends opened into loops), `time` (round length in seconds), `mode` (as `MAZE_MODE`)
+//! and `map`, a handcrafted map file (see `mapfile`) relative to the playlist that replaces
+//! the generated maze.
+
+use std::path::Path;
+use std::time::Duration;
+
+use protocol::maze::{self, Algorithm};
+use protocol::{Difficulty, MapSize};
+use rand::seq::SliceRandom;
+
+use crate::mapfile::MapFile;
+use crate::rules::GameMode;
+
+/// How the next level is picked when a round ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    /// In file order, starting over after the last level.
+    #[default]
+    Loop,
+    /// Every level once in random order, then again in a new order.
+    Shuffle,
+    /// The level most voted for in the lobby; the next in file order without votes.
+    Vote,
+}
+
+/// One playlist entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub name: String,
+    pub size: MapSize,
+    pub algorithm: Algorithm,
+    /// Announced in `Round`; only `dead_ends` changes the maze.
+    pub difficulty: Difficulty,
+    /// Percent of dead ends opened into loops.
+    pub dead_ends: usize,
+    /// Round length; the server's `RoundRules` time limit when unset.
+    pub time_limit: Option<Duration>,
+    /// The server's game mode when unset.
+    pub mode: Option<GameMode>,
+    /// Handcrafted maze played instead of a generated one.
+    pub map: Option<MapFile>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playlist {
+    pub levels: Vec<Level>,
+    pub order: Order,
+    /// Index of the level being played, `None` before the first round.
+    current: Option<usize>,
+    /// Shuffled levels still to come in this pass, next last.
+    upcoming: Vec<usize>,
+}
+
+impl Playlist {
+    /// Reads a playlist file, and every map file it names.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Parses playlist text; map files are looked up relative to `base`.
+    pub fn parse(text: &str, base: &Path) -> Result<Self, String> {
+        let mut playlist = Playlist { levels: Vec::new(), order: Order::Loop, current: None, upcoming: Vec::new() };
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let (keyword, rest) = line.split_once(char::is_whitespace).map_or((line, ""), |(k, r)| (k, r.trim()));
+            match keyword {
+                "order" => {
+                    playlist.order = match rest {
+                        "loop" => Order::Loop,
+                        "shuffle" => Order::Shuffle,
+                        "vote" => Order::Vote,
+                        _ => return Err(format!("line {}: unknown order '{rest}'; expected loop, shuffle or vote", n + 1)),
+                    }
+                }
+                "level" => playlist.levels.push(parse_level(rest, base).map_err(|e| format!("line {}: {e}", n + 1))?),
+                _ => return Err(format!("line {}: expected 'order' or 'level', found '{keyword}'", n + 1)),
+            }
+        }
+        if playlist.levels.is_empty() {
+            return Err("the playlist has no levels".into());
+        }
+        Ok(playlist)
+    }
+
+    /// The level being played and its index.
+    pub fn current(&self) -> Option<(usize, &Level)> {
+        self.current.map(|i| (i, &self.levels[i]))
+    }
+
+    /// Index of the level named `text`, or numbered `text` counting from 1.
+    pub fn find(&self, text: &str) -> Option<usize> {
+        match text.parse::<usize>() {
+            Ok(number) => (1..=self.levels.len()).contains(&number).then(|| number - 1),
+            Err(_) => self.levels.iter().position(|l| l.name.eq_ignore_ascii_case(text)),
+        }
+    }
+
+    /// Moves on to the next level and returns its index. `vote` is the lobby's choice; only
+    /// `Order::Vote` listens to it.
+    pub fn advance(&mut self, vote: Option<usize>) -> usize {
+        let following = self.current.map_or(0, |i| (i + 1) % self.levels.len());
+        let next = match self.order {
+            Order::Loop => following,
+            Order::Vote => vote.filter(|i| *i < self.levels.len()).unwrap_or(following),
+            Order::Shuffle => {
+                if self.upcoming.is_empty() {
+                    self.upcoming = (0..self.levels.len()).collect();
+                    self.upcoming.shuffle(&mut rand::rng());
+                    // A new pass must not start with the level just played
+                    if self.upcoming.len() > 1 && self.upcoming.last() == self.current.as_ref() {
+                        self.upcoming.swap(0, self.levels.len() - 1);
+                    }
+                }
+                self.upcoming.pop().unwrap_or(following)
+            }
+        };
+        self.current = Some(next);
+        next
+    }
+}
+
+/// Parses `<name> = <settings>`; a bare name keeps every default.
+fn parse_level(text: &str, base: &Path) -> Result<Level, String> {
+    let (name, settings) = text.split_once('=').map_or((text, ""), |(n, s)| (n.trim(), s.trim()));
+    if name.is_empty() {
+        return Err("the level needs a name".into());
+    }
+    let mut level = Level {
+        name: name.to_string(),
+        size: MapSize::default(),
+        algorithm: Algorithm::default(),
+        difficulty: Difficulty::Medium,
+        dead_ends: maze::dead_end_percent(Difficulty::Medium),
+        time_limit: None,
+        mode: None,
+        map: None,
+    };
+    let mut dead_ends = None;
+    for part in settings.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (key, value) = part.split_once(':').ok_or(format!("expected key:value, found '{part}'"))?;
+        let invalid = || format!("invalid {key} '{value}'");
+        match key {
+            "size" => level.size = MapSize::ALL.into_iter().find(|s| s.as_str() == value).ok_or_else(invalid)?,
+            "algorithm" => level.algorithm = Algorithm::parse(value).ok_or_else(invalid)?,
+            "difficulty" => level.difficulty = Difficulty::parse(value).ok_or_else(invalid)?,
+            "dead_ends" => dead_ends = Some(value.parse().ok().filter(|p| *p <= 100).ok_or_else(invalid)?),
+            "time" => level.time_limit = Some(Duration::from_secs(value.parse().ok().filter(|s| *s > 0).ok_or_else(invalid)?)),
+            "mode" => level.mode = Some(GameMode::parse(value).ok_or_else(invalid)?),
+            "map" => level.map = Some(MapFile::load(&base.join(value))?),
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+    }
+    level.dead_ends = dead_ends.unwrap_or(maze::dead_end_percent(level.difficulty));
+    Ok(level)
+}
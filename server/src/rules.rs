@@ -0,0 +1,217 @@
+//! How rounds start and end, and who plays against whom. `tick_rounds` checks the end
+//! conditions every tick while a round is running; whichever fires first decides the winner.
+
+use std::time::Duration;
+
+use protocol::RoundEnd;
+use rand::Rng;
+
+/// When the lobby hands over to a round, and when that round ends. The time limit always
+/// applies; the other end conditions are optional.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundRules {
+    /// First player to reach this score wins.
+    pub score_limit: Option<i32>,
+    /// Elimination: the dead stay dead until the next round, and the last one alive wins.
+    pub last_standing: bool,
+    /// Each maze gets an exit cell; the first player to step on it wins.
+    pub exit: bool,
+    /// Round length; the top scorer wins when it runs out.
+    pub time_limit: Duration,
+    /// Share of players (0 to 1) that must be ready before the lobby ends early.
+    pub ready_quorum: f32,
+    /// Longest the lobby waits for players to ready up.
+    pub lobby_timeout: Duration,
+}
+
+impl Default for RoundRules {
+    fn default() -> Self {
+        Self {
+            score_limit: Some(500),
+            last_standing: false,
+            exit: false,
+            time_limit: Duration::from_secs(30),
+            ready_quorum: 1.0,
+            lobby_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RoundRules {
+    /// Parses a comma separated list such as `score:500,exit,time:120`, e.g. from `MAZE_RULES`.
+    /// `last` enables elimination. End conditions not listed are off; the time limit and the
+    /// lobby settings (`quorum:<share>`, `lobby:<seconds>`) keep their defaults unless given.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut rules = RoundRules { score_limit: None, ..Default::default() };
+        for part in text.split(',').map(str::trim) {
+            match part.split_once(':') {
+                None if part == "last" => rules.last_standing = true,
+                None if part == "exit" => rules.exit = true,
+                Some(("score", limit)) => rules.score_limit = Some(limit.parse().ok().filter(|l| *l > 0)?),
+                Some(("time", secs)) => rules.time_limit = Duration::from_secs(secs.parse().ok().filter(|s| *s > 0)?),
+                Some(("quorum", share)) => rules.ready_quorum = share.parse().ok().filter(|q| (0.0..=1.0).contains(q))?,
+                Some(("lobby", secs)) => rules.lobby_timeout = Duration::from_secs(secs.parse().ok()?),
+                _ => return None,
+            }
+        }
+        Some(rules)
+    }
+
+    /// Ready players needed to start with `players` in the lobby; never less than one.
+    pub fn ready_needed(&self, players: usize) -> usize {
+        ((players as f32 * self.ready_quorum).ceil() as usize).max(1)
+    }
+}
+
+/// What the end conditions look at of one player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contender {
+    pub id: u64,
+    pub team: Option<u8>,
+    pub score: i32,
+    pub alive: bool,
+    pub cell: (usize, usize),
+}
+
+/// Whether a running round is over: why, and the winning player and team if any. The time
+/// limit comes last, so a kill on the final tick still wins on score. `exit` is the goal cell
+/// when the exit rule is on, and `time_up` is set once the time limit has run out.
+pub fn round_end(
+    rules: &RoundRules,
+    mode: GameMode,
+    players: &[Contender],
+    exit: Option<(usize, usize)>,
+    time_up: bool,
+) -> Option<(RoundEnd, Option<u64>, Option<u8>)> {
+    if let Some(teams) = mode.teams() {
+        return team_round_end(rules, teams, players, exit, time_up);
+    }
+    if let Some(limit) = rules.score_limit {
+        if let Some(p) = players.iter().filter(|p| p.score >= limit).max_by_key(|p| p.score) {
+            return Some((RoundEnd::ScoreLimit, Some(p.id), None));
+        }
+    }
+    if rules.last_standing && players.len() >= 2 {
+        let mut alive = players.iter().filter(|p| p.alive);
+        if let (first, None) = (alive.next(), alive.next()) {
+            return Some((RoundEnd::LastStanding, first.map(|p| p.id), None));
+        }
+    }
+    if let Some(p) = exit.and_then(|exit| players.iter().find(|p| p.alive && p.cell == exit)) {
+        return Some((RoundEnd::ReachedExit, Some(p.id), None));
+    }
+    time_up.then(|| (RoundEnd::TimeLimit, leader(players), None))
+}
+
+/// Team version of `round_end`: the limits apply to team totals, the last team with anyone
+/// alive wins an elimination, and reaching the exit wins for the runner's team.
+fn team_round_end(
+    rules: &RoundRules,
+    teams: u8,
+    players: &[Contender],
+    exit: Option<(usize, usize)>,
+    time_up: bool,
+) -> Option<(RoundEnd, Option<u64>, Option<u8>)> {
+    // (team, players, score), best score first
+    let mut totals: Vec<(u8, usize, i32)> = (0..teams)
+        .map(|team| {
+            let members = players.iter().filter(|p| p.team == Some(team));
+            (team, members.clone().count(), members.map(|p| p.score).sum())
+        })
+        .collect();
+    totals.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+    if let Some(limit) = rules.score_limit {
+        if let Some(&(team, _, _)) = totals.first().filter(|t| t.2 >= limit) {
+            return Some((RoundEnd::ScoreLimit, None, Some(team)));
+        }
+    }
+    if rules.last_standing && totals.iter().filter(|t| t.1 > 0).count() >= 2 {
+        let mut alive: Vec<u8> = players.iter().filter(|p| p.alive).filter_map(|p| p.team).collect();
+        alive.sort_unstable();
+        alive.dedup();
+        if alive.len() <= 1 {
+            return Some((RoundEnd::LastStanding, None, alive.first().copied()));
+        }
+    }
+    if let Some(p) = exit.and_then(|exit| players.iter().find(|p| p.alive && p.cell == exit)) {
+        return Some((RoundEnd::ReachedExit, Some(p.id), p.team));
+    }
+    time_up.then(|| {
+        let team = match totals.as_slice() {
+            [first, second, ..] if first.2 == second.2 => None,
+            [first, ..] => Some(first.0),
+            [] => None,
+        };
+        (RoundEnd::TimeLimit, None, team)
+    })
+}
+
+/// The single top scorer; `None` when nobody plays or the top score is shared.
+fn leader(players: &[Contender]) -> Option<u64> {
+    let best = players.iter().map(|p| p.score).max()?;
+    let mut top = players.iter().filter(|p| p.score == best);
+    match (top.next(), top.next()) {
+        (Some(p), None) => Some(p.id),
+        _ => None,
+    }
+}
+
+/// Votes per option, in the order of `options`.
+pub fn tally<T: Copy + PartialEq>(votes: &[T], options: &[T]) -> Vec<(T, u32)> {
+    options.iter().map(|o| (*o, votes.iter().filter(|v| *v == o).count() as u32)).collect()
+}
+
+/// The most voted option, a random one among ties; `None` without votes.
+pub fn vote_winner<T: Copy + PartialEq>(votes: &[T], options: &[T]) -> Option<T> {
+    let tally = tally(votes, options);
+    let best = tally.iter().map(|(_, n)| *n).max().filter(|n| *n > 0)?;
+    let tied: Vec<T> = tally.into_iter().filter(|(_, n)| *n == best).map(|(o, _)| o).collect();
+    Some(tied[rand::rng().random_range(0..tied.len())])
+}
+
+/// Who fights whom.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GameMode {
+    /// Everyone on their own.
+    #[default]
+    FreeForAll,
+    /// Team deathmatch: players are balanced into `teams` on join, spawn on their team's side
+    /// of the maze and score for their team.
+    Teams { teams: u8, friendly_fire: bool },
+}
+
+impl GameMode {
+    /// Parses `ffa`, `teams` (two teams) or `teams:<count>`, with an optional `:ff` suffix
+    /// enabling friendly fire, e.g. from `MAZE_MODE`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (text, friendly_fire) = match text.strip_suffix(":ff") {
+            Some(rest) => (rest, true),
+            None => (text, false),
+        };
+        match text.split_once(':') {
+            None if text == "ffa" && !friendly_fire => Some(GameMode::FreeForAll),
+            None if text == "teams" => Some(GameMode::Teams { teams: 2, friendly_fire }),
+            Some(("teams", count)) => {
+                let teams = count.parse().ok().filter(|n| (2..=8).contains(n))?;
+                Some(GameMode::Teams { teams, friendly_fire })
+            }
+            _ => None,
+        }
+    }
+
+    /// Number of teams, `None` in free-for-all.
+    pub fn teams(self) -> Option<u8> {
+        match self {
+            GameMode::FreeForAll => None,
+            GameMode::Teams { teams, .. } => Some(teams),
+        }
+    }
+
+    /// Whether `shooter` may damage `target`; teammates only with friendly fire on.
+    pub fn can_damage(self, shooter: Option<u8>, target: Option<u8>) -> bool {
+        match self {
+            GameMode::FreeForAll => true,
+            GameMode::Teams { friendly_fire, .. } => friendly_fire || shooter != target,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Interest management: which players each client gets to see.
+//! Snapshots only carry players the receiving client could plausibly see, so a modified
+//! client cannot reveal everyone hiding in the maze.
+
+use protocol::snapshot::PlayerTable;
+
+/// Replication rule for other players' positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// Everyone sees everyone, like a full minimap. For casual modes.
+    Full,
+    /// Players in an unobstructed line of sight, plus anyone within `radius` cells
+    /// (Chebyshev distance) regardless of walls.
+    LineOfSight { radius: usize },
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::LineOfSight { radius: 2 }
+    }
+}
+
+impl Visibility {
+    /// Parses `full`, `los` or `los:<radius>`, e.g. from `MAZE_VISIBILITY`.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.split_once(':') {
+            None if text == "full" => Some(Visibility::Full),
+            None if text == "los" => Some(Visibility::default()),
+            Some(("los", radius)) => radius.parse().ok().map(|radius| Visibility::LineOfSight { radius }),
+            _ => None,
+        }
+    }
+
+    /// The part of `table` that `viewer` may see; the viewer itself is always included.
+    pub fn filter(self, table: &PlayerTable, viewer: u64, cells: &[u8], width: usize) -> PlayerTable {
+        if self == Visibility::Full { return table.clone(); }
+        let Some(me) = table.get(&viewer) else { return PlayerTable::new() };
+        table
+            .iter()
+            .filter(|(id, p)| **id == viewer || self.can_see((me.x, me.y), (p.x, p.y), cells, width))
+            .map(|(id, p)| (*id, *p))
+            .collect()
+    }
+
+    /// Whether a player standing at `from` sees the cell `to`.
+    pub fn can_see(self, from: (usize, usize), to: (usize, usize), cells: &[u8], width: usize) -> bool {
+        match self {
+            Visibility::Full => true,
+            Visibility::LineOfSight { radius } => {
+                from.0.abs_diff(to.0).max(from.1.abs_diff(to.1)) <= radius || line_of_sight(cells, width, from, to)
+            }
+        }
+    }
+}
+
+/// True when every cell on the grid line between `from` and `to` is open. Diagonal steps
+/// must not squeeze between two walls touching at a corner.
+pub fn line_of_sight(cells: &[u8], width: usize, from: (usize, usize), to: (usize, usize)) -> bool {
+    let open = |x: i64, y: i64| cells.get(y as usize * width + x as usize) == Some(&0);
+    let (mut x, mut y) = (from.0 as i64, from.1 as i64);
+    let (tx, ty) = (to.0 as i64, to.1 as i64);
+    let (dx, dy) = ((tx - x).abs(), -(ty - y).abs());
+    let (sx, sy) = ((tx - x).signum(), (ty - y).signum());
+    let mut err = dx + dy;
+    // Bresenham; both endpoints hold players, so they are open
+    while (x, y) != (tx, ty) {
+        let e2 = 2 * err;
+        let step_x = e2 >= dy;
+        let step_y = e2 <= dx;
+        if step_x && step_y && !open(x + sx, y) && !open(x, y + sy) {
+            return false;
+        }
+        if step_x { err += dy; x += sx; }
+        if step_y { err += dx; y += sy; }
+        if !open(x, y) {
+            return false;
+        }
+    }
+    true
+}
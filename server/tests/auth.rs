@@ -0,0 +1,69 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use protocol::{ClientMessage, Codec, ServerFrame, ServerMessage, PROTOCOL_VERSION};
+use renet::{ConnectionConfig, DefaultChannel, RenetClient};
+use renet_netcode::{ClientAuthentication, ConnectToken, NetcodeClientTransport};
+use server::{auth, spawn, MazeWarsServerPlugin};
+
+#[test]
+fn issuer_answers_while_another_client_stalls() {
+    let key = auth::generate_key();
+    // Tokens name a server that is never dialled here
+    let issuer = auth::spawn_token_issuer("127.0.0.1:0", key, vec![([127, 0, 0, 1], 5000).into()]).unwrap();
+
+    // Connects and never sends its username
+    let _stalled = TcpStream::connect(issuer).unwrap();
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(issuer).unwrap();
+    writeln!(stream, "alice").unwrap();
+    let mut bytes = Vec::new();
+    stream.read_to_end(&mut bytes).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2), "waited {:?} behind the stalled client", started.elapsed());
+    assert!(ConnectToken::read(&mut bytes.as_slice()).is_ok(), "expected a token, got {:?}", String::from_utf8_lossy(&bytes));
+}
+
+#[test]
+fn token_without_username_is_refused() {
+    let key = auth::generate_key();
+    let handle = spawn(MazeWarsServerPlugin { bind_addr: "127.0.0.1:0".into(), private_key: Some(key), ..Default::default() });
+    let server = handle.addr;
+
+    let named = auth::issue_token(&key, vec![server], "alice").unwrap();
+    assert!(welcomed(named), "a token with a username should be let in");
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let nameless = ConnectToken::generate(now, PROTOCOL_VERSION.protocol_id(), 300, 7, 15, vec![server], None, &key).unwrap();
+    assert!(!welcomed(nameless), "a token without a username should be refused");
+}
+
+/// Connects with `token`, says hello and reports whether a `Welcome` came back.
+fn welcomed(token: ConnectToken) -> bool {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_nonblocking(true).unwrap();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let mut transport = NetcodeClientTransport::new(now, ClientAuthentication::Secure { connect_token: token }, socket).unwrap();
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    let hello = ClientMessage::Hello { version: PROTOCOL_VERSION, build: "auth test".into(), name: "mallory".into(), codec: Codec::Json, resume: None };
+    client.send_message(DefaultChannel::ReliableOrdered, hello.to_bytes(Codec::Json));
+
+    let deadline = Instant::now() + Duration::from_secs(3);
+    let mut last_update = Instant::now();
+    while Instant::now() < deadline {
+        let dt = last_update.elapsed();
+        last_update = Instant::now();
+        client.update(dt);
+        if transport.update(dt, &mut client).is_err() || client.is_disconnected() {
+            return false;
+        }
+        while let Some(bytes) = client.receive_message(DefaultChannel::ReliableOrdered) {
+            if let Ok(ServerFrame { message: ServerMessage::Welcome { .. }, .. }) = ServerFrame::from_bytes(&bytes) {
+                return true;
+            }
+        }
+        let _ = transport.send_packets(&mut client);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use protocol::{Difficulty, MapSize, MAX_CHAT_LEN};
+use server::chat::{clean, ChatLimiter, Command, HELP};
+
+#[test]
+fn parses_ready() {
+    assert_eq!(Command::parse("ready"), Ok(Command::Ready(true)));
+    assert_eq!(Command::parse("ready on"), Ok(Command::Ready(true)));
+    assert_eq!(Command::parse("ready off"), Ok(Command::Ready(false)));
+    assert_eq!(Command::parse("unready"), Ok(Command::Ready(false)));
+    assert!(Command::parse("ready maybe").is_err());
+}
+
+#[test]
+fn parses_votes() {
+    assert_eq!(Command::parse("vote hard"), Ok(Command::VoteDifficulty(Difficulty::Hard)));
+    assert_eq!(Command::parse("vote difficulty medium"), Ok(Command::VoteDifficulty(Difficulty::Medium)));
+    assert_eq!(Command::parse("vote large"), Ok(Command::VoteMapSize(MapSize::Large)));
+    // `medium` alone is a difficulty, so the size needs spelling out
+    assert_eq!(Command::parse("vote medium"), Ok(Command::VoteDifficulty(Difficulty::Medium)));
+    assert_eq!(Command::parse("vote size medium"), Ok(Command::VoteMapSize(MapSize::Medium)));
+    assert_eq!(Command::parse("vote level  Team arena "), Ok(Command::VoteLevel("Team arena".into())));
+    assert_eq!(Command::parse("vote level 2"), Ok(Command::VoteLevel("2".into())));
+    let err = Command::parse("vote purple").unwrap_err();
+    assert!(err.contains("purple"), "{err}");
+    assert!(Command::parse("vote").is_err());
+}
+
+#[test]
+fn parses_the_rest() {
+    assert_eq!(Command::parse("levels"), Ok(Command::Levels));
+    assert_eq!(Command::parse("who"), Ok(Command::Who));
+    assert_eq!(Command::parse("team push left"), Ok(Command::Team("push left".into())));
+    assert_eq!(Command::parse("team"), Err("usage: /team <text>".into()));
+    assert_eq!(Command::parse("help"), Ok(Command::Help));
+    assert_eq!(Command::parse("help vote"), Ok(Command::Help));
+    assert!(Command::parse("who me").is_err());
+    let err = Command::parse("kick bob").unwrap_err();
+    assert!(err.contains("/kick") && err.contains("/help"), "{err}");
+    assert!(HELP.iter().all(|line| line.starts_with('/')));
+}
+
+#[test]
+fn clean_trims_and_drops_control_characters() {
+    assert_eq!(clean("  hello\tthere\u{7}\n"), "hellothere");
+    assert_eq!(clean("\u{1b}[31mred"), "[31mred");
+    assert_eq!(clean("   "), "");
+    assert_eq!(clean("héllo wörld"), "héllo wörld");
+}
+
+#[test]
+fn clean_cuts_long_lines_on_characters() {
+    let long = "é".repeat(MAX_CHAT_LEN + 10);
+    let cleaned = clean(&long);
+    assert_eq!(cleaned.chars().count(), MAX_CHAT_LEN);
+    assert!(cleaned.chars().all(|c| c == 'é'));
+}
+
+#[test]
+fn chat_limiter_allows_a_burst_then_one_line_per_refill() {
+    let mut limiter = ChatLimiter::default();
+    let now = Instant::now();
+    assert_eq!((0..10).filter(|_| limiter.try_take_at(now)).count(), 5);
+    assert!(!limiter.try_take_at(now + ChatLimiter::INTERVAL / 2));
+    assert!(limiter.try_take_at(now + ChatLimiter::INTERVAL));
+    assert_eq!(ChatLimiter::INTERVAL, Duration::from_secs(2));
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use server::collision::{resolve, Collision};
+
+/// A 7x3 maze whose only open cells are the one-wide corridor (1..=5, 1).
+const WIDTH: usize = 7;
+const HEIGHT: usize = 3;
+
+fn corridor() -> Vec<u8> {
+    let mut cells = vec![1u8; WIDTH * HEIGHT];
+    for x in 1..=5 {
+        cells[WIDTH + x] = 0;
+    }
+    cells
+}
+
+fn at(entries: &[(u64, usize)]) -> BTreeMap<u64, (usize, usize)> {
+    entries.iter().map(|&(id, x)| (id, (x, 1))).collect()
+}
+
+fn run(rule: Collision, positions: &[(u64, usize)], moves: &[(u64, usize)]) -> BTreeMap<u64, (usize, usize)> {
+    resolve(rule, &corridor(), WIDTH, HEIGHT, &at(positions), &at(moves))
+}
+
+#[test]
+fn head_on_swap_is_refused() {
+    for rule in [Collision::Block, Collision::Push] {
+        let result = run(rule, &[(1, 2), (2, 3)], &[(1, 3), (2, 2)]);
+        assert_eq!(result, at(&[(1, 2), (2, 3)]), "{rule:?}");
+    }
+}
+
+#[test]
+fn overlap_lets_players_pass_through() {
+    let result = run(Collision::Overlap, &[(1, 2), (2, 3)], &[(1, 3), (2, 2)]);
+    assert_eq!(result, at(&[(1, 3), (2, 2)]));
+}
+
+#[test]
+fn walking_into_a_standing_player_is_refused() {
+    let result = run(Collision::Block, &[(1, 2), (2, 3)], &[(1, 3)]);
+    assert_eq!(result, at(&[(1, 2), (2, 3)]));
+}
+
+#[test]
+fn contested_cell_goes_to_the_lower_id() {
+    // Both orders of insertion give the same outcome
+    let result = run(Collision::Block, &[(7, 4), (3, 2)], &[(7, 3), (3, 3)]);
+    assert_eq!(result, at(&[(3, 3), (7, 4)]));
+}
+
+#[test]
+fn following_a_player_who_moves_away_is_allowed() {
+    let result = run(Collision::Block, &[(1, 1), (2, 2), (3, 3)], &[(1, 2), (2, 3), (3, 4)]);
+    assert_eq!(result, at(&[(1, 2), (2, 3), (3, 4)]));
+}
+
+#[test]
+fn a_refused_move_blocks_the_players_behind_it() {
+    // 3 walks into 4 head-on; 1 and 2 were following 3
+    let result = run(Collision::Block, &[(1, 1), (2, 2), (3, 3), (4, 4)], &[(1, 2), (2, 3), (3, 4), (4, 3)]);
+    assert_eq!(result, at(&[(1, 1), (2, 2), (3, 3), (4, 4)]));
+}
+
+#[test]
+fn push_shoves_a_standing_player_along() {
+    let result = run(Collision::Push, &[(1, 2), (2, 3)], &[(1, 3)]);
+    assert_eq!(result, at(&[(1, 3), (2, 4)]));
+}
+
+#[test]
+fn push_into_a_wall_is_refused() {
+    let result = run(Collision::Push, &[(1, 4), (2, 5)], &[(1, 5)]);
+    assert_eq!(result, at(&[(1, 4), (2, 5)]));
+}
+
+#[test]
+fn push_into_another_player_is_refused() {
+    let result = run(Collision::Push, &[(1, 2), (2, 3), (3, 4)], &[(1, 3)]);
+    assert_eq!(result, at(&[(1, 2), (2, 3), (3, 4)]));
+}
+
+#[test]
+fn parse_names() {
+    assert_eq!(Collision::parse("overlap"), Some(Collision::Overlap));
+    assert_eq!(Collision::parse("block"), Some(Collision::Block));
+    assert_eq!(Collision::parse("push"), Some(Collision::Push));
+    assert_eq!(Collision::parse("shove"), None);
+}
@@ -0,0 +1,92 @@
+//! Bare netcode client for tests that talk to a running server.
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use protocol::{ClientMessage, Codec, PlayerState, ServerFrame, ServerMessage, PROTOCOL_VERSION};
+use renet::{ConnectionConfig, DefaultChannel, RenetClient};
+use renet_netcode::{ClientAuthentication, NetcodeClientTransport};
+
+/// Bare netcode client speaking JSON.
+pub struct TestClient {
+    client: RenetClient,
+    transport: NetcodeClientTransport,
+    last_update: Instant,
+    /// Received but not yet waited for, oldest first.
+    inbox: VecDeque<ServerMessage>,
+}
+
+impl TestClient {
+    pub fn connect(server_addr: SocketAddr, client_id: u64) -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let auth = ClientAuthentication::Unsecure {
+            protocol_id: PROTOCOL_VERSION.protocol_id(),
+            client_id,
+            server_addr,
+            user_data: None,
+        };
+        let transport = NetcodeClientTransport::new(now, auth, socket).unwrap();
+        Self { client: RenetClient::new(ConnectionConfig::default()), transport, last_update: Instant::now(), inbox: VecDeque::new() }
+    }
+
+    pub fn hello(&mut self, name: &str, resume: Option<u64>) {
+        let hello = ClientMessage::Hello { version: PROTOCOL_VERSION, build: "test client".into(), name: name.into(), codec: Codec::Json, resume };
+        self.send(&hello);
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) {
+        self.client.send_message(DefaultChannel::ReliableOrdered, message.to_bytes(Codec::Json));
+    }
+
+    fn pump(&mut self) {
+        let dt = self.last_update.elapsed();
+        self.last_update = Instant::now();
+        self.client.update(dt);
+        let _ = self.transport.update(dt, &mut self.client);
+        while let Some(bytes) = self.client.receive_message(DefaultChannel::ReliableOrdered) {
+            if let Ok(ServerFrame { message, .. }) = ServerFrame::from_bytes(&bytes) {
+                self.inbox.push_back(message);
+            }
+        }
+        // Fails until netcode has connected; renet keeps the messages queued meanwhile
+        let _ = self.transport.send_packets(&mut self.client);
+    }
+
+    /// Pumps until `pick` accepts a message; the messages before it are dropped.
+    pub fn wait_for<T>(&mut self, what: &str, mut pick: impl FnMut(ServerMessage) -> Option<T>) -> T {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            self.pump();
+            while let Some(message) = self.inbox.pop_front() {
+                if let Some(found) = pick(message) {
+                    return found;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out waiting for {what}");
+    }
+
+    pub fn welcome(&mut self) -> (u64, bool) {
+        self.wait_for("Welcome", |m| match m {
+            ServerMessage::Welcome { session, resumed, .. } => Some((session, resumed)),
+            _ => None,
+        })
+    }
+
+    pub fn init(&mut self) -> PlayerState {
+        self.wait_for("PlayerInit", |m| match m {
+            ServerMessage::PlayerInit(state) => Some(state),
+            _ => None,
+        })
+    }
+
+    pub fn disconnect(&mut self) {
+        self.client.disconnect();
+        self.transport.disconnect();
+    }
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use std::path::Path;
+
+use common::TestClient;
+use protocol::{ClientMessage, RoundState, ServerMessage};
+use server::playlist::Playlist;
+use server::{spawn, MazeWarsServerPlugin};
+
+/// A 5x5 handcrafted level.
+const TINY: &str = "\
+name: Tiny
+#####
+#S..#
+#.#.#
+#...#
+#####
+";
+
+#[test]
+fn handcrafted_level_after_a_maze_announces_no_seed() {
+    let text = format!("level Maze = size:small,dead_ends:35,time:1\nlevel Tiny = map:{}", write_map());
+    let playlist = Playlist::parse(&text, Path::new("/")).unwrap();
+    let server = spawn(MazeWarsServerPlugin { bind_addr: "127.0.0.1:0".into(), playlist: Some(playlist), ..Default::default() });
+    let map = |m| match m {
+        ServerMessage::Map { width, seed, dead_ends, .. } => Some((width, seed, dead_ends)),
+        _ => None,
+    };
+
+    let mut alice = TestClient::connect(server.addr, 1);
+    alice.hello("alice", None);
+    alice.welcome();
+    alice.wait_for("the lobby maze", map);
+    alice.send(&ClientMessage::Ready { ready: true });
+    let (_, seed, dead_ends) = alice.wait_for("the maze level", map);
+    assert!(seed.is_some());
+    assert_eq!(dead_ends, 35);
+
+    // The one-second round runs out; ready up again once the lobby is back
+    alice.wait_for("the next lobby", |m| matches!(m, ServerMessage::Round { state: RoundState::Lobby, .. }).then_some(()));
+    alice.send(&ClientMessage::Ready { ready: true });
+    assert_eq!(alice.wait_for("the handcrafted level", map), (5, None, 0));
+}
+
+fn write_map() -> String {
+    let path = std::env::temp_dir().join(format!("mazewars-levels-{}.txt", std::process::id()));
+    std::fs::write(&path, TINY).unwrap();
+    path.display().to_string()
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use protocol::maze::{generate, Algorithm};
+use protocol::Difficulty;
+use server::mapfile::MapFile;
+
+const ROOM: &str = "\
+name: Room
+author: tester
+#####
+#S..#
+#.#.#
+#..S#
+#####
+";
+
+#[test]
+fn parses_header_cells_and_spawns() {
+    let map = MapFile::parse(ROOM).unwrap();
+    assert_eq!((map.name.as_str(), map.author.as_deref()), ("Room", Some("tester")));
+    assert_eq!((map.width, map.height), (5, 5));
+    assert_eq!(map.spawns, [(1, 1), (3, 3)]);
+    assert_eq!(&map.cells[5..10], &[1, 0, 0, 0, 1]);
+    assert_eq!(map.cells[2 * 5 + 2], 1);
+}
+
+#[test]
+fn dump_reads_back_the_same_map() {
+    let map = MapFile::parse(ROOM).unwrap();
+    assert_eq!(map.to_string(), ROOM);
+    assert_eq!(MapFile::parse(&map.to_string()).unwrap(), map);
+}
+
+#[test]
+fn generated_mazes_dump_to_valid_maps() {
+    for algorithm in Algorithm::ALL {
+        let cells = generate(5, 21, 15, Difficulty::Easy, algorithm);
+        let map = MapFile::from_cells("dump", 21, 15, cells.clone());
+        let back = MapFile::parse(&map.to_string()).unwrap_or_else(|e| panic!("{algorithm:?}: {e}"));
+        assert_eq!(back.cells, cells);
+        assert!(back.spawns.is_empty());
+    }
+}
+
+#[test]
+fn rejects_broken_maps() {
+    let error = |text: &str| MapFile::parse(text).unwrap_err();
+    assert_eq!(error("#####\n#...#\n#####\n"), "the header needs a 'name: ...' line");
+    assert_eq!(error("name: A\nsize: 5\n###\n#.#\n###\n"), "line 2: expected 'name: ...' or 'author: ...' before the map, found 'size: 5'");
+    assert_eq!(error("name: A\n###\n###\n"), "the map needs at least 3 rows of 3 cells");
+    assert_eq!(error("name: A\n####\n#..#\n###\n"), "line 4: row is 3 cells wide, the first is 4");
+    assert_eq!(error("name: A\n###\n#x#\n###\n"), "line 3: unexpected 'x'; use # for walls, . for passages and S for spawns");
+    assert_eq!(error("name: A\n###\n#..\n###\n"), "the border must be all walls, but (2, 1) is open");
+    assert_eq!(error("name: A\n###\n###\n###\n"), "the map has no passages");
+    assert_eq!(error("name: A\n#####\n#.#.#\n#####\n"), "(3, 1) cannot be reached from (1, 1)");
+}
+
+#[test]
+fn example_map_is_valid() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("maps/crossroads.txt");
+    let map = MapFile::load(&path).unwrap();
+    assert_eq!(map.name, "Crossroads");
+    assert_eq!(map.spawns.len(), 9);
+}
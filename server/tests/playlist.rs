@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::time::Duration;
+
+use protocol::maze::Algorithm;
+use protocol::{Difficulty, MapSize};
+use server::playlist::{Order, Playlist};
+use server::rules::GameMode;
+
+const PLAYLIST: &str = "\
+# three levels
+order vote
+level Warm-up = size:small,algorithm:prim,difficulty:easy,time:60
+level Arena = dead_ends:35,mode:teams:3:ff
+level Plain
+";
+
+fn parse(text: &str) -> Result<Playlist, String> {
+    Playlist::parse(text, Path::new("."))
+}
+
+#[test]
+fn parses_levels_and_settings() {
+    let playlist = parse(PLAYLIST).unwrap();
+    assert_eq!(playlist.order, Order::Vote);
+    let names: Vec<&str> = playlist.levels.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, ["Warm-up", "Arena", "Plain"]);
+
+    let warm_up = &playlist.levels[0];
+    assert_eq!((warm_up.size, warm_up.algorithm, warm_up.difficulty), (MapSize::Small, Algorithm::Prim, Difficulty::Easy));
+    assert_eq!(warm_up.dead_ends, 50);
+    assert_eq!(warm_up.time_limit, Some(Duration::from_secs(60)));
+
+    let arena = &playlist.levels[1];
+    assert_eq!(arena.dead_ends, 35);
+    assert_eq!(arena.mode, Some(GameMode::Teams { teams: 3, friendly_fire: true }));
+
+    let plain = &playlist.levels[2];
+    assert_eq!((plain.size, plain.difficulty, plain.time_limit, plain.mode), (MapSize::Medium, Difficulty::Medium, None, None));
+}
+
+#[test]
+fn rejects_bad_lines() {
+    assert_eq!(parse("").unwrap_err(), "the playlist has no levels");
+    assert_eq!(parse("level A\norder random").unwrap_err(), "line 2: unknown order 'random'; expected loop, shuffle or vote");
+    assert_eq!(parse("level A = size:huge").unwrap_err(), "line 1: invalid size 'huge'");
+    assert_eq!(parse("level A = dead_ends:101").unwrap_err(), "line 1: invalid dead_ends '101'");
+    assert_eq!(parse("level A = colour:red").unwrap_err(), "line 1: unknown setting 'colour'");
+    assert_eq!(parse("maze A").unwrap_err(), "line 1: expected 'order' or 'level', found 'maze'");
+}
+
+#[test]
+fn loop_order_wraps_around() {
+    let mut playlist = parse("level A\nlevel B\nlevel C").unwrap();
+    let order: Vec<usize> = (0..5).map(|_| playlist.advance(Some(2))).collect();
+    assert_eq!(order, [0, 1, 2, 0, 1]);
+}
+
+#[test]
+fn vote_order_follows_votes_then_the_file() {
+    let mut playlist = parse(PLAYLIST).unwrap();
+    assert_eq!(playlist.advance(Some(2)), 2);
+    assert_eq!(playlist.advance(None), 0);
+    assert_eq!(playlist.current().map(|(_, l)| l.name.as_str()), Some("Warm-up"));
+}
+
+#[test]
+fn shuffle_plays_every_level_once_per_pass() {
+    let mut playlist = parse("order shuffle\nlevel A\nlevel B\nlevel C\nlevel D").unwrap();
+    let mut previous = None;
+    for _ in 0..10 {
+        let mut pass: Vec<usize> = (0..4).map(|_| playlist.advance(None)).collect();
+        assert_ne!(Some(pass[0]), previous, "a pass repeats the last level");
+        previous = pass.last().copied();
+        pass.sort_unstable();
+        assert_eq!(pass, [0, 1, 2, 3]);
+    }
+}
+
+#[test]
+fn finds_levels_by_number_or_name() {
+    let playlist = parse(PLAYLIST).unwrap();
+    assert_eq!(playlist.find("2"), Some(1));
+    assert_eq!(playlist.find("warm-up"), Some(0));
+    assert_eq!(playlist.find("4"), None);
+    assert_eq!(playlist.find("0"), None);
+}
+
+#[test]
+fn example_playlist_loads() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("playlists/example.txt");
+    assert_eq!(Playlist::load(&path).unwrap().levels.len(), 5);
+}
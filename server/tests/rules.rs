@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use protocol::RoundEnd;
+use server::rules::{round_end, tally, vote_winner, Contender, GameMode, RoundRules};
+
+const FFA: GameMode = GameMode::FreeForAll;
+const TEAMS: GameMode = GameMode::Teams { teams: 2, friendly_fire: false };
+
+fn rules(text: &str) -> RoundRules {
+    RoundRules::parse(text).unwrap()
+}
+
+fn player(id: u64, team: Option<u8>, score: i32) -> Contender {
+    Contender { id, team, score, alive: true, cell: (id as usize, 1) }
+}
+
+fn dead(p: Contender) -> Contender {
+    Contender { alive: false, ..p }
+}
+
+#[test]
+fn nothing_ends_a_quiet_round() {
+    let players = [player(1, None, 10), player(2, None, 20)];
+    assert_eq!(round_end(&rules("score:100,last,exit"), FFA, &players, Some((9, 9)), false), None);
+    assert_eq!(round_end(&rules("time:30"), FFA, &[], None, false), None);
+}
+
+#[test]
+fn score_limit_goes_to_the_top_scorer() {
+    let players = [player(1, None, 120), player(2, None, 150), player(3, None, 90)];
+    assert_eq!(round_end(&rules("score:100"), FFA, &players, None, false), Some((RoundEnd::ScoreLimit, Some(2), None)));
+    // It beats the time limit running out on the same tick
+    assert_eq!(round_end(&rules("score:100"), FFA, &players, None, true), Some((RoundEnd::ScoreLimit, Some(2), None)));
+}
+
+#[test]
+fn last_standing_needs_two_players() {
+    let rules = rules("last");
+    assert_eq!(round_end(&rules, FFA, &[player(1, None, 0)], None, false), None);
+    let players = [player(1, None, 0), dead(player(2, None, 0)), dead(player(3, None, 0))];
+    assert_eq!(round_end(&rules, FFA, &players, None, false), Some((RoundEnd::LastStanding, Some(1), None)));
+    // Nobody left: a draw
+    let players = [dead(player(1, None, 0)), dead(player(2, None, 0))];
+    assert_eq!(round_end(&rules, FFA, &players, None, false), Some((RoundEnd::LastStanding, None, None)));
+}
+
+#[test]
+fn reaching_the_exit_wins_when_alive() {
+    let exit = Some((2, 1));
+    let players = [player(1, None, 0), player(2, None, 0)];
+    assert_eq!(round_end(&rules("exit"), FFA, &players, exit, false), Some((RoundEnd::ReachedExit, Some(2), None)));
+    let players = [player(1, None, 0), dead(player(2, None, 0))];
+    assert_eq!(round_end(&rules("exit"), FFA, &players, exit, false), None);
+}
+
+#[test]
+fn time_limit_crowns_a_single_leader() {
+    let rules = rules("time:30");
+    assert_eq!(rules.time_limit, Duration::from_secs(30));
+    let players = [player(1, None, 10), player(2, None, 30)];
+    assert_eq!(round_end(&rules, FFA, &players, None, true), Some((RoundEnd::TimeLimit, Some(2), None)));
+    let tied = [player(1, None, 30), player(2, None, 30), player(3, None, 5)];
+    assert_eq!(round_end(&rules, FFA, &tied, None, true), Some((RoundEnd::TimeLimit, None, None)));
+    assert_eq!(round_end(&rules, FFA, &[], None, true), Some((RoundEnd::TimeLimit, None, None)));
+}
+
+#[test]
+fn teams_win_on_their_totals() {
+    // Neither player of team 0 reaches 100 alone, but together they do
+    let players = [player(1, Some(0), 60), player(2, Some(0), 50), player(3, Some(1), 90)];
+    assert_eq!(round_end(&rules("score:100"), TEAMS, &players, None, false), Some((RoundEnd::ScoreLimit, None, Some(0))));
+    assert_eq!(round_end(&rules("time:30"), TEAMS, &players, None, true), Some((RoundEnd::TimeLimit, None, Some(0))));
+    let tied = [player(1, Some(0), 50), player(2, Some(1), 50)];
+    assert_eq!(round_end(&rules("time:30"), TEAMS, &tied, None, true), Some((RoundEnd::TimeLimit, None, None)));
+}
+
+#[test]
+fn last_team_standing_wins() {
+    let rules = rules("last");
+    let players = [player(1, Some(0), 0), dead(player(2, Some(0), 0)), dead(player(3, Some(1), 0)), dead(player(4, Some(1), 0))];
+    assert_eq!(round_end(&rules, TEAMS, &players, None, false), Some((RoundEnd::LastStanding, None, Some(0))));
+    // One alive on each side keeps it going
+    let players = [player(1, Some(0), 0), player(3, Some(1), 0), dead(player(4, Some(1), 0))];
+    assert_eq!(round_end(&rules, TEAMS, &players, None, false), None);
+    // A single team has nobody to fight yet
+    assert_eq!(round_end(&rules, TEAMS, &[player(1, Some(0), 0), dead(player(2, Some(0), 0))], None, false), None);
+}
+
+#[test]
+fn reaching_the_exit_wins_for_the_team() {
+    let players = [player(1, Some(0), 0), player(2, Some(1), 0)];
+    assert_eq!(round_end(&rules("exit"), TEAMS, &players, Some((2, 1)), false), Some((RoundEnd::ReachedExit, Some(2), Some(1))));
+}
+
+#[test]
+fn ready_quorum_rounds_up_and_needs_someone() {
+    let all = rules("quorum:1");
+    assert_eq!(all.ready_needed(0), 1);
+    assert_eq!(all.ready_needed(5), 5);
+    let half = rules("quorum:0.5");
+    assert_eq!(half.ready_needed(1), 1);
+    assert_eq!(half.ready_needed(3), 2);
+    assert_eq!(half.ready_needed(4), 2);
+    assert_eq!(rules("quorum:0").ready_needed(6), 1);
+    assert_eq!(RoundRules::parse("quorum:1.5"), None);
+}
+
+#[test]
+fn tally_counts_listed_options_in_order() {
+    assert_eq!(tally(&['b', 'a', 'b', 'z'], &['a', 'b', 'c']), vec![('a', 1), ('b', 2), ('c', 0)]);
+}
+
+#[test]
+fn most_votes_win() {
+    assert_eq!(vote_winner(&[2, 3, 2], &[1, 2, 3]), Some(2));
+    assert_eq!(vote_winner::<u32>(&[], &[1, 2, 3]), None);
+    // Votes for options not on offer do not count
+    assert_eq!(vote_winner(&[9, 9], &[1, 2, 3]), None);
+}
+
+#[test]
+fn ties_are_broken_among_the_tied_only() {
+    let mut picked = [false; 4];
+    for _ in 0..200 {
+        let winner = vote_winner(&[1, 3, 1, 3, 2], &[0, 1, 2, 3]).unwrap();
+        assert!(winner == 1 || winner == 3, "{winner}");
+        picked[winner] = true;
+    }
+    assert!(picked[1] && picked[3], "a tie should not always go the same way");
+}
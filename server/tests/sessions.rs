@@ -0,0 +1,55 @@
+mod common;
+
+use std::path::Path;
+
+use common::TestClient;
+use protocol::{ClientMessage, RoundState, ServerMessage};
+use server::playlist::Playlist;
+use server::{spawn, MazeWarsServerPlugin};
+
+/// A 5x5 level with one spawn marker, much smaller than the lobby's generated maze.
+const TINY: &str = "\
+name: Tiny
+#####
+#S..#
+#.#.#
+#...#
+#####
+";
+
+#[test]
+fn resumed_player_is_moved_onto_the_new_level() {
+    let playlist = Playlist::parse(&format!("level Tiny = map:{}", write_map()), Path::new("/")).unwrap();
+    let server = spawn(MazeWarsServerPlugin { bind_addr: "127.0.0.1:0".into(), playlist: Some(playlist), ..Default::default() });
+
+    // Alice joins the lobby maze, then drops and is parked
+    let mut alice = TestClient::connect(server.addr, 1);
+    alice.hello("alice", None);
+    let (session, _) = alice.welcome();
+    let parked_at = alice.init();
+    assert!(parked_at.x >= 5 || parked_at.y >= 5, "the lobby spawn should be off the tiny map");
+    alice.disconnect();
+
+    // Bob starts a round on the tiny map while she is away
+    let mut bob = TestClient::connect(server.addr, 2);
+    bob.hello("bob", None);
+    bob.welcome();
+    bob.send(&ClientMessage::Ready { ready: true });
+    bob.wait_for("the round", |m| matches!(m, ServerMessage::Round { state: RoundState::InRound, .. }).then_some(()));
+
+    let mut back = TestClient::connect(server.addr, 3);
+    back.hello("alice", Some(session));
+    let (_, resumed) = back.welcome();
+    assert!(resumed);
+    let state = back.init();
+    let open = TINY.lines().skip(1).nth(state.y).and_then(|row| row.chars().nth(state.x)).is_some_and(|c| c != '#');
+    assert!(open, "resumed on ({}, {}), not a passage of the tiny map", state.x, state.y);
+    // Bob holds the only spawn marker
+    assert_ne!((state.x, state.y), (1, 1));
+}
+
+fn write_map() -> String {
+    let path = std::env::temp_dir().join(format!("mazewars-sessions-{}.txt", std::process::id()));
+    std::fs::write(&path, TINY).unwrap();
+    path.display().to_string()
+}
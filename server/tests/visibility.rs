@@ -0,0 +1,88 @@
+use protocol::snapshot::PlayerTable;
+use protocol::{Facing, PlayerState};
+use server::visibility::{line_of_sight, Visibility};
+
+/// A 7x7 maze: `#` wall, `.` open.
+const WIDTH: usize = 7;
+const MAZE: &str = "\
+#######
+#.....#
+#.#.#.#
+#.#...#
+#.###.#
+#....##
+#######";
+
+fn cells() -> Vec<u8> {
+    MAZE.lines().flat_map(|row| row.chars().map(|c| u8::from(c == '#'))).collect()
+}
+
+fn player(id: u64, x: usize, y: usize) -> PlayerState {
+    PlayerState { id, x, y, facing: Facing::North, last_input: 0, team: None, motion: None }
+}
+
+#[test]
+fn open_corridors_are_seen_both_ways() {
+    let cells = cells();
+    for (from, to) in [((1, 1), (5, 1)), ((1, 1), (1, 5)), ((3, 1), (3, 3)), ((3, 3), (5, 3))] {
+        assert!(line_of_sight(&cells, WIDTH, from, to), "{from:?} -> {to:?}");
+        assert!(line_of_sight(&cells, WIDTH, to, from), "{to:?} -> {from:?}");
+        assert!(line_of_sight(&cells, WIDTH, from, from));
+    }
+}
+
+#[test]
+fn walls_block_the_line() {
+    let cells = cells();
+    // (2, 2) stands between them
+    assert!(!line_of_sight(&cells, WIDTH, (1, 3), (3, 1)));
+    assert!(!line_of_sight(&cells, WIDTH, (1, 5), (5, 3)));
+    assert!(!line_of_sight(&cells, WIDTH, (3, 3), (1, 5)));
+}
+
+#[test]
+fn diagonals_cannot_squeeze_between_corner_walls() {
+    let corners: Vec<u8> = "\
+#####
+#.#.#
+##.##
+#.#.#
+#####"
+        .lines()
+        .flat_map(|row| row.chars().map(|c| u8::from(c == '#')))
+        .collect();
+    assert!(!line_of_sight(&corners, 5, (1, 1), (2, 2)));
+    assert!(!line_of_sight(&corners, 5, (3, 3), (1, 1)));
+    // One open side is enough
+    assert!(line_of_sight(&cells(), WIDTH, (3, 2), (4, 3)));
+}
+
+#[test]
+fn radius_sees_through_walls() {
+    let cells = cells();
+    let rule = Visibility::LineOfSight { radius: 2 };
+    assert!(rule.can_see((1, 3), (3, 1), &cells, WIDTH));
+    assert!(!Visibility::LineOfSight { radius: 1 }.can_see((1, 3), (3, 1), &cells, WIDTH));
+    assert!(!rule.can_see((1, 5), (5, 3), &cells, WIDTH));
+    assert!(Visibility::Full.can_see((1, 5), (5, 3), &cells, WIDTH));
+}
+
+#[test]
+fn filter_keeps_the_viewer_and_who_it_sees() {
+    let cells = cells();
+    let table: PlayerTable = [player(1, 1, 5), player(2, 1, 1), player(3, 5, 3), player(4, 2, 5)].into_iter().map(|p| (p.id, p)).collect();
+    let seen = Visibility::LineOfSight { radius: 0 }.filter(&table, 1, &cells, WIDTH);
+    assert_eq!(seen.keys().copied().collect::<Vec<_>>(), vec![1, 2, 4]);
+    assert_eq!(Visibility::Full.filter(&table, 1, &cells, WIDTH), table);
+    // A viewer without a player sees nobody
+    assert!(Visibility::default().filter(&table, 9, &cells, WIDTH).is_empty());
+}
+
+#[test]
+fn parses_rules() {
+    assert_eq!(Visibility::parse("full"), Some(Visibility::Full));
+    assert_eq!(Visibility::parse("los"), Some(Visibility::default()));
+    assert_eq!(Visibility::parse("los:5"), Some(Visibility::LineOfSight { radius: 5 }));
+    assert_eq!(Visibility::parse("los:x"), None);
+    assert_eq!(Visibility::parse("fog"), None);
+}